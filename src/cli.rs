@@ -0,0 +1,375 @@
+use rusqlite::{Connection, ErrorCode, OptionalExtension};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::{get_by_remote_id, insert_data, open_database, rename_task, sanitize_name, set_remote_id, task_exists, RenameOutcome, SOURCE_CAPTURE, SOURCE_DEMO};
+
+
+/** Default location of the task database, --db overrides it */
+pub const DEFAULT_DB_PATH: &str = "./src/resources/db/tasks.db";
+
+
+/** Used for storing the parsed command line, anything that is not a known flag belongs to the headless command */
+pub struct Options {
+    pub db_path: String,
+    pub inspect: bool,
+    // opens the list showing only tasks changed within this many days
+    pub recent_days: Option<usize>,
+    // fills an empty list with sample tasks before opening it
+    pub demo: bool,
+    pub command: Vec<String>
+}
+
+
+/** Used for reporting why a headless command failed, main exits with the code of the error so scripts can branch on it.
+ * 0 success, 2 bad usage, 3 task not found, 4 name already taken, 5 database error, 6 file could not be opened
+ */
+pub enum CliError {
+    Usage(String),
+    NotFound(String),
+    Conflict(String),
+    Database(rusqlite::Error),
+    Io(String)
+}
+
+
+impl CliError {
+    /** Used for mapping the error to the process exit code */
+    pub fn exit_code(&self) -> i32 {
+        return match self {
+            CliError::Usage(_) => 2,
+            CliError::NotFound(_) => 3,
+            CliError::Conflict(_) => 4,
+            CliError::Database(_) => 5,
+            CliError::Io(_) => 6
+        };
+    }
+}
+
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            CliError::Usage(message) | CliError::NotFound(message) | CliError::Conflict(message) | CliError::Io(message) => write!(f, "{message}"),
+            CliError::Database(error) => write!(f, "Database error: {error}")
+        };
+    }
+}
+
+
+impl From<rusqlite::Error> for CliError {
+    fn from(error: rusqlite::Error) -> Self {
+        // sqlite failing to open the file is a filesystem problem rather than a problem with the database itself
+        if error.sqlite_error_code() == Some(ErrorCode::CannotOpen) {
+            return CliError::Io(error.to_string());
+        }
+        return CliError::Database(error);
+    }
+}
+
+
+/** Used for parsing the command line arguments. Flags go before the headless command, everything from the command word on belongs
+ * to the command so a captured task name can contain words like --db
+ */
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Options {
+    let mut options = Options {
+        db_path: DEFAULT_DB_PATH.to_string(),
+        inspect: false,
+        recent_days: None,
+        demo: false,
+        command: Vec::new()
+    };
+    while let Some(arg) = args.next() {
+        if !options.command.is_empty() {
+            options.command.push(arg);
+            continue;
+        }
+        match arg.as_str() {
+            "--db" => {
+                if let Some(path) = args.next() {
+                    options.db_path = path;
+                }
+            }
+            "--inspect" => options.inspect = true,
+            "--demo" => options.demo = true,
+            "--recent" => options.recent_days = args.next().and_then(|days| days.parse().ok()),
+            _ => options.command.push(arg)
+        }
+    }
+    return options;
+}
+
+
+/** Used for retrieving the value following a flag of a headless command, e.g. the 3 in `--id 3` */
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    return args.iter().position(|arg| arg == flag).and_then(|index| args.get(index + 1)).map(|value| value.as_str());
+}
+
+
+/** Headless commands that only read the database, the only ones that can be run with --inspect */
+const READ_ONLY_COMMANDS: [&str; 2] = ["find-remote-id", "dump-sql"];
+
+
+/** Used for refusing a headless command that would change a database opened with --inspect */
+pub fn check_inspect(command: &[String]) -> Result<(), CliError> {
+    if READ_ONLY_COMMANDS.contains(&command[0].as_str()) {
+        return Ok(());
+    }
+    return Err(CliError::Usage(format!("'{}' can't be run with --inspect, only {} can", command[0], READ_ONLY_COMMANDS.join(" and "))));
+}
+
+
+/** Used for running a headless command instead of opening the list, returns the message to print on success */
+pub fn run(conn: &Connection, command: &[String]) -> Result<String, CliError> {
+    return match command[0].as_str() {
+        "edit" => edit(conn, &command[1..]),
+        "set-remote-id" => set_remote(conn, &command[1..]),
+        "find-remote-id" => find_remote(conn, &command[1..]),
+        "duplicate-list" => duplicate_list(conn, &command[1..]),
+        "capture" => capture(conn, &command[1..]),
+        "dump-sql" => dump_sql(conn, &command[1..]),
+        "apply-sql" => apply_sql(conn, &command[1..]),
+        "clear-demo" => clear_demo(conn),
+        other => Err(CliError::Usage(format!("Unknown command '{other}'")))
+    };
+}
+
+
+/** Used for renaming a task without opening the list, `edit (--id <n> | --task <old name>) --name <new name>` */
+fn edit(conn: &Connection, args: &[String]) -> Result<String, CliError> {
+    let Some(new_name) = flag_value(args, "--name") else {
+        return Err(CliError::Usage("Usage: edit (--id <n> | --task <name>) --name <new name>".to_string()));
+    };
+    let Some(new_name) = sanitize_name(new_name) else {
+        return Err(CliError::Usage("The new task name can't be empty".to_string()));
+    };
+    let new_name = new_name.as_str();
+    // the task can be picked either by its id or by its current name
+    let old_name = match (flag_value(args, "--id"), flag_value(args, "--task")) {
+        (Some(id), _) => {
+            let id: i64 = id.parse().map_err(|_| CliError::Usage(format!("'{id}' is not a valid task id")))?;
+            conn.query_row("SELECT name FROM tasks WHERE id = ?1", [id], |row| row.get::<_, String>(0))
+                .optional()?
+                .ok_or(CliError::NotFound(format!("No task with id {id}")))?
+        }
+        (None, Some(name)) => name.to_string(),
+        (None, None) => return Err(CliError::Usage("Either --id or --task is required".to_string()))
+    };
+    // there is no one to confirm a merge with, so a name collision is skipped and reported
+    return match rename_task(conn, &old_name, new_name)? {
+        RenameOutcome::Renamed => Ok(format!("Renamed '{old_name}' to '{new_name}'")),
+        RenameOutcome::NotFound => Err(CliError::NotFound(format!("No task named '{old_name}'"))),
+        RenameOutcome::NameTaken => Err(CliError::Conflict(format!("Skipped renaming '{old_name}', a task named '{new_name}' already exists")))
+    };
+}
+
+
+/** Used for linking a task to an external service, `set-remote-id --id <n> --remote-id <remote id>` */
+fn set_remote(conn: &Connection, args: &[String]) -> Result<String, CliError> {
+    let (Some(id), Some(remote_id)) = (flag_value(args, "--id"), flag_value(args, "--remote-id")) else {
+        return Err(CliError::Usage("Usage: set-remote-id --id <n> --remote-id <remote id>".to_string()));
+    };
+    let id: i64 = id.parse().map_err(|_| CliError::Usage(format!("'{id}' is not a valid task id")))?;
+    if let Some(task) = get_by_remote_id(conn, remote_id)? && task.id != id {
+        return Err(CliError::Conflict(format!("Remote id '{remote_id}' is already linked to task {}", task.id)));
+    }
+    if !set_remote_id(conn, id, remote_id)? {
+        return Err(CliError::NotFound(format!("No task with id {id}")));
+    }
+    return Ok(format!("Linked task {id} to remote id '{remote_id}'"));
+}
+
+
+/** Used for looking up the task linked to an external service, `find-remote-id --remote-id <remote id>` */
+fn find_remote(conn: &Connection, args: &[String]) -> Result<String, CliError> {
+    let Some(remote_id) = flag_value(args, "--remote-id") else {
+        return Err(CliError::Usage("Usage: find-remote-id --remote-id <remote id>".to_string()));
+    };
+    let task = get_by_remote_id(conn, remote_id)?
+        .ok_or(CliError::NotFound(format!("No task linked to remote id '{remote_id}'")))?;
+    let status = if task.completed { "completed" } else { "open" };
+    return Ok(format!("{}\t{}\t{status}\t{}\t{}", task.id, task.name, task.remote_id.unwrap_or_default(), task.synced_at.unwrap_or_default()));
+}
+
+
+/** Used for copying every task into a new list file to start a new cycle, `duplicate-list --to <path> [--reset] [--overwrite]`.
+ * --reset reopens every task in the copy, a target that already has tasks is only replaced with --overwrite
+ */
+fn duplicate_list(conn: &Connection, args: &[String]) -> Result<String, CliError> {
+
+    // Nested function for copying the tasks into the attached target in one transaction, links to external services stay with the original list
+    fn copy_tasks(conn: &Connection, reset: bool) -> Result<usize, rusqlite::Error> {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM target.tasks", [])?;
+        let copied = tx.execute(
+            "INSERT INTO target.tasks (id, name, completed, blocked_by, completed_at, sort_order, source, someday, effort, created_at)
+            SELECT id, name, CASE WHEN ?1 THEN FALSE ELSE completed END, blocked_by, CASE WHEN ?1 THEN NULL ELSE completed_at END,
+                sort_order, source, someday, effort, created_at
+            FROM main.tasks", [reset])?;
+        tx.commit()?;
+        return Ok(copied);
+    }
+
+    let Some(target) = flag_value(args, "--to") else {
+        return Err(CliError::Usage("Usage: duplicate-list --to <path> [--reset] [--overwrite]".to_string()));
+    };
+    let reset = args.iter().any(|arg| arg == "--reset");
+    let overwrite = args.iter().any(|arg| arg == "--overwrite");
+    // overwriting the list with itself would only empty it
+    let source = conn.path().and_then(|path| std::fs::canonicalize(path).ok());
+    if source.is_some() && source == std::fs::canonicalize(target).ok() {
+        return Err(CliError::Usage("The list can't be duplicated onto itself".to_string()));
+    }
+    // opening the target first creates it and brings it up to the current schema
+    let existing: usize = open_database(target)?.query_row("SELECT COUNT(*) FROM tasks", [], |row| row.get(0))?;
+    if existing > 0 && !overwrite {
+        return Err(CliError::Conflict(format!("'{target}' already has {existing} tasks, add --overwrite to replace them")));
+    }
+    conn.execute("ATTACH DATABASE ?1 AS target", [target])?;
+    let copied = copy_tasks(conn, reset);
+    conn.execute("DETACH DATABASE target", [])?;
+    return Ok(format!("Copied {} tasks to '{target}'", copied?));
+}
+
+
+/** Used for adding a task as fast as possible, e.g. from a system-wide hotkey, `capture <task name>`.
+ * Every argument is part of the name so it doesn't have to be quoted, nothing is printed on success
+ */
+fn capture(conn: &Connection, args: &[String]) -> Result<String, CliError> {
+    let Some(name) = sanitize_name(&args.join(" ")) else {
+        return Err(CliError::Usage("Usage: capture <task name>".to_string()));
+    };
+    if task_exists(conn, &name)? {
+        return Err(CliError::Conflict(format!("A task named '{name}' already exists")));
+    }
+    insert_data(conn, &name, SOURCE_CAPTURE)?;
+    return Ok(String::new());
+}
+
+
+/** Used for removing the sample tasks added by --demo, `clear-demo`. Tasks added or captured since are kept */
+fn clear_demo(conn: &Connection) -> Result<String, CliError> {
+    let removed = conn.execute("DELETE FROM tasks WHERE source = ?1", [SOURCE_DEMO])?;
+    return Ok(format!("Removed {removed} sample tasks"));
+}
+
+
+/** Used for writing a lossless backup of the whole database as sql statements, `dump-sql --to <path>`.
+ * Tables come first, then their rows, then indexes and triggers so replaying the rows doesn't fire the triggers
+ */
+fn dump_sql(conn: &Connection, args: &[String]) -> Result<String, CliError> {
+
+    // Nested function for building the statements, sqlite's quote() writes every value back exactly as it is stored
+    fn statements(conn: &Connection) -> Result<Vec<String>, rusqlite::Error> {
+        let mut dump = vec!["PRAGMA foreign_keys = OFF;".to_string()];
+        let mut stmt = conn.prepare("SELECT type, name, sql FROM sqlite_master WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+            ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'index' THEN 1 ELSE 2 END, rowid")?;
+        let schema = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?
+            .collect::<Result<Vec<(String, String, String)>, rusqlite::Error>>()?;
+        let tables: Vec<&String> = schema.iter().filter(|(kind, _, _)| kind == "table").map(|(_, name, _)| name).collect();
+        for (_, _, sql) in schema.iter().filter(|(kind, _, _)| kind == "table") {
+            dump.push(format!("{sql};"));
+        }
+        for table in &tables {
+            let quoted = format!("\"{}\"", table.replace('"', "\"\""));
+            let columns = conn.prepare(&format!("SELECT * FROM {quoted}"))?.column_names().iter()
+                .map(|column| format!("quote(\"{}\")", column.replace('"', "\"\"")))
+                .collect::<Vec<String>>()
+                .join(" || ',' || ");
+            let mut rows = conn.prepare(&format!("SELECT 'INSERT INTO ' || ?1 || ' VALUES (' || {columns} || ');' FROM {quoted}"))?;
+            for row in rows.query_map([&quoted], |row| row.get::<_, String>(0))? {
+                dump.push(row?);
+            }
+        }
+        // autoincrement counters, so ids of deleted tasks aren't handed out again after restoring
+        if conn.query_row("SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'sqlite_sequence')", [], |row| row.get(0))? {
+            dump.push("DELETE FROM sqlite_sequence;".to_string());
+            let mut rows = conn.prepare("SELECT 'INSERT INTO sqlite_sequence VALUES (' || quote(name) || ',' || quote(seq) || ');' FROM sqlite_sequence")?;
+            for row in rows.query_map([], |row| row.get::<_, String>(0))? {
+                dump.push(row?);
+            }
+        }
+        for (_, _, sql) in schema.iter().filter(|(kind, _, _)| kind != "table") {
+            dump.push(format!("{sql};"));
+        }
+        dump.push("PRAGMA foreign_keys = ON;".to_string());
+        return Ok(dump);
+    }
+
+    let Some(path) = flag_value(args, "--to") else {
+        return Err(CliError::Usage("Usage: dump-sql --to <path>".to_string()));
+    };
+    let dump = statements(conn)?;
+    std::fs::write(path, format!("-- rusty_todo_list database dump, restore with apply-sql --from <path>\n{}\n", dump.join("\n")))
+        .map_err(|error| CliError::Io(format!("Couldn't write '{path}': {error}")))?;
+    return Ok(format!("Dumped the database to '{path}'"));
+}
+
+
+/** Used for restoring a dump written by dump-sql, `apply-sql --from <path> [--yes]`. Every table is replaced by what the file creates.
+ * The file can run any sql, so it asks before applying it unless --yes is given, and nothing changes if any statement fails
+ */
+fn apply_sql(conn: &Connection, args: &[String]) -> Result<String, CliError> {
+
+    // Nested function for dropping every table and running the dump in its place, in one transaction
+    fn replace_tables(conn: &Connection, sql: &str) -> Result<(), rusqlite::Error> {
+        let tx = conn.unchecked_transaction()?;
+        let tables = tx.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+        for table in tables {
+            tx.execute(&format!("DROP TABLE \"{}\"", table.replace('"', "\"\"")), [])?;
+        }
+        tx.execute_batch(sql)?;
+        tx.commit()?;
+        return Ok(());
+    }
+
+    let Some(path) = flag_value(args, "--from") else {
+        return Err(CliError::Usage("Usage: apply-sql --from <path> [--yes]".to_string()));
+    };
+    let sql = std::fs::read_to_string(path).map_err(|error| CliError::Io(format!("Couldn't read '{path}': {error}")))?;
+    if !args.iter().any(|arg| arg == "--yes") {
+        eprint!("This runs every statement in '{path}' and replaces all tables of '{}'. Continue? [y/N] ", conn.path().unwrap_or_default());
+        io::stderr().flush().ok();
+        let mut answer = String::new();
+        io::stdin().lock().read_line(&mut answer).ok();
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            return Err(CliError::Usage("Nothing was applied".to_string()));
+        }
+    }
+    // rows are restored before the blockers they point at exist, foreign keys can only be switched outside of a transaction
+    conn.pragma_update(None, "foreign_keys", false)?;
+    let applied = replace_tables(conn, &sql);
+    conn.pragma_update(None, "foreign_keys", true)?;
+    applied?;
+    return Ok(format!("Applied '{path}'"));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /** Used for parsing a command line written as one string */
+    fn parse(line: &str) -> Options {
+        return parse_args(line.split(' ').map(str::to_string));
+    }
+
+
+    #[test]
+    fn flags_stop_at_the_command() {
+        let options = parse("--db /tmp/tasks.db --inspect capture call --demo mom --db home");
+        assert_eq!(options.db_path, "/tmp/tasks.db");
+        assert!(options.inspect);
+        assert!(!options.demo);
+        assert_eq!(options.command, ["capture", "call", "--demo", "mom", "--db", "home"]);
+        assert!(check_inspect(&options.command).is_err_and(|error| error.exit_code() == 2));
+        assert!(check_inspect(&["dump-sql".to_string(), "--to".to_string(), "backup.sql".to_string()]).is_ok());
+        let options = parse("--recent 3 --demo");
+        assert_eq!(options.recent_days, Some(3));
+        assert!(options.demo);
+        assert!(options.command.is_empty());
+    }
+}
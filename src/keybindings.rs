@@ -0,0 +1,143 @@
+use cursive::event::{Event, Key};
+use rusqlite::Connection;
+
+use crate::settings;
+
+
+/** Actions that can be bound to a key along with their default key, an empty default leaves the action unbound.
+ * A binding is changed with the `key.<action>` setting, e.g. `key.quit`, and an empty value unbinds the action.
+ */
+pub const ACTIONS: [(&str, &str); 21] = [
+    ("quit", "q"),
+    ("debug_console", "~"),
+    ("toggle_ids", "I"),
+    ("dismiss_greeting", "Esc"),
+    ("add", ""),
+    ("edit", ""),
+    ("delete", ""),
+    ("block", ""),
+    ("reset", ""),
+    ("toggle_recent", "R"),
+    ("filter_source", "S"),
+    ("details", "d"),
+    ("someday", "z"),
+    ("someday_view", "Z"),
+    ("type_ahead", "/"),
+    ("toggle_status", "x"),
+    ("sort_effort", "e"),
+    ("export_html", "H"),
+    ("toggle_stale", "O"),
+    ("review", "W"),
+    ("replace", "F")
+];
+
+
+/** Used for loading the key bound to every action along with every invalid or conflicting binding.
+ * An invalid binding falls back to the default key of its action and a conflicting binding leaves its action unbound
+ */
+pub fn load(conn: &Connection) -> (Vec<(&'static str, Event)>, Vec<String>) {
+    let mut bindings: Vec<(&'static str, Event)> = Vec::new();
+    let mut problems: Vec<String> = Vec::new();
+    for (action, default) in ACTIONS {
+        let setting = format!("key.{action}");
+        let key = settings::get(conn, &setting).unwrap_or(default.to_string());
+        if key.is_empty() {
+            continue;
+        }
+        let Some(event) = parse_key(&key).or_else(|| {
+            problems.push(format!("{setting} = '{key}' is not a key, using '{default}'"));
+            parse_key(default)
+        }) else {
+            continue;
+        };
+        // a key can only run one action, so both bindings are reported instead of one of them silently winning
+        if let Some((other, _)) = bindings.iter().find(|(_, bound)| *bound == event) {
+            problems.push(format!("{setting} = '{key}' is already bound to key.{other}, {action} is unbound"));
+            continue;
+        }
+        bindings.push((action, event));
+    }
+    return (bindings, problems);
+}
+
+
+/** Used for parsing a key written in the settings, a single character, a named key like `Esc` or `F5`, or `Ctrl-x`/`Alt-x` */
+fn parse_key(key: &str) -> Option<Event> {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(Event::Char(c));
+    }
+    if let Some(c) = single_char(key.strip_prefix("Ctrl-")) {
+        return Some(Event::CtrlChar(c));
+    }
+    if let Some(c) = single_char(key.strip_prefix("Alt-")) {
+        return Some(Event::AltChar(c));
+    }
+    if let Some(n) = key.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()).filter(|n| (1..=12).contains(n)) {
+        return Some(Event::Key(Key::from_f(n)));
+    }
+    let named = match key {
+        "Esc" => Key::Esc,
+        "Enter" => Key::Enter,
+        "Tab" => Key::Tab,
+        "Backspace" => Key::Backspace,
+        "Del" => Key::Del,
+        "Ins" => Key::Ins,
+        "Home" => Key::Home,
+        "End" => Key::End,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        _ => return None
+    };
+    return Some(Event::Key(named));
+}
+
+
+/** Used for taking the character out of a modifier binding like the x of `Ctrl-x` */
+fn single_char(rest: Option<&str>) -> Option<char> {
+    let mut chars = rest?.chars();
+    return match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None
+    };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /** Used for opening an empty settings table in memory holding the given key bindings */
+    fn bindings_with(values: &[(&str, &str)]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        settings::create_table(&conn).unwrap();
+        for (key, value) in values {
+            settings::set(&conn, key, value);
+        }
+        return conn;
+    }
+
+
+    /** Used for looking up the key an action ended up bound to */
+    fn bound(bindings: &[(&'static str, Event)], action: &str) -> Option<Event> {
+        return bindings.iter().find(|(bound, _)| *bound == action).map(|(_, event)| event.clone());
+    }
+
+
+    #[test]
+    fn load_reports_invalid_and_conflicting_keys() {
+        let conn = bindings_with(&[("key.quit", "Ctrl-"), ("key.details", "q"), ("key.add", "Ctrl-a"), ("key.someday", ""), ("key.unknown", "u")]);
+        let (bindings, problems) = load(&conn);
+        assert_eq!(problems, [
+            "key.quit = 'Ctrl-' is not a key, using 'q'",
+            "key.details = 'q' is already bound to key.quit, details is unbound"
+        ]);
+        assert_eq!(bound(&bindings, "quit"), Some(Event::Char('q')));
+        assert_eq!(bound(&bindings, "details"), None);
+        assert_eq!(bound(&bindings, "add"), Some(Event::CtrlChar('a')));
+        // an empty value unbinds an action on purpose, so it isn't a problem
+        assert_eq!(bound(&bindings, "someday"), None);
+        assert_eq!(bound(&bindings, "sort_effort"), Some(Event::Char('e')));
+    }
+}
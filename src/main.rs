@@ -1,141 +1,1725 @@
-use cursive::utils::span::SpannedString;
+#![allow(clippy::needless_return)]
+
 use cursive::Cursive;
-use cursive::view::{Nameable, Resizable, Scrollable};
-use cursive::views::{Button, Dialog, EditView, LinearLayout, SelectView};
+use cursive::view::{Nameable, Resizable, Scrollable, SizeConstraint};
+use cursive::views::{Button, Checkbox, Dialog, EditView, HideableView, LinearLayout, NamedView, OnEventView, SelectView, TextView};
 use cursive_async_view::{AsyncProgressView, AsyncProgressState};
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Result};
 use std::{time};
+use std::sync::{Arc, Mutex};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
 use cursive::utils::markup::StyledString;
+use cursive::style::{Color, ColorStyle, Effect, Style};
+use cursive::event::{Callback, Event, EventResult, EventTrigger, Key, MouseButton, MouseEvent};
+use unicode_width::UnicodeWidthChar;
+
+mod cli;
+mod keybindings;
+mod report;
+mod settings;
+
+
+
+
+
+/** Used for storing todo list task data */
+struct Task {
+    id: i64,
+    name: String,
+    completed: bool,
+    // true when the task is blocked by another task that is not completed yet
+    blocked: bool,
+    // id of the task in an external service and when it was last synced, reserved for integrations and not shown in the list
+    remote_id: Option<String>,
+    synced_at: Option<String>,
+    // whole days since the task was created, None for tasks from before creation times were kept
+    age_days: Option<i64>
+}
+
+
+/** Used for storing state shared between cursive callbacks, stored as the cursive user data */
+struct AppData {
+    // read only connection used for everything the list displays, writes go through open_write
+    conn: Connection,
+    db_path: String,
+    // when true all mutating actions are disabled, set by the --inspect flag
+    inspect: bool,
+    // when true the add dialog opens again after adding a task, remembered for the rest of the session
+    add_another: bool,
+    // how many columns a task label may take before it is cut off with an ellipsis, recomputed when the terminal is resized
+    label_width: usize,
+    labels: LabelOptions,
+    // how many tasks the list loads, only set when a very large list is limited at startup
+    limit: Option<usize>,
+    filter: Filter,
+    // shared with the key handler of the list so the type_ahead binding can switch it on
+    type_ahead: Arc<Mutex<TypeAhead>>
+}
+
+
+/** Used for storing what was typed to jump to a task, the list only jumps while this is active so typing doesn't trigger key bindings */
+#[derive(Default)]
+struct TypeAhead {
+    active: bool,
+    typed: String,
+    last_key: Option<time::Instant>
+}
+
+
+/** Used for storing how task labels are built */
+#[derive(Clone)]
+struct LabelOptions {
+    // when true every row starts with the task id, handy for headless commands taking --id
+    show_ids: bool,
+    completed: CompletedStyle,
+    // when true the first rows are numbered and the number keys toggle them
+    numbered: bool,
+    // open tasks older than this many days are marked as stale, 0 disables the marker
+    stale_days: usize
+}
+
+
+/** Used for storing how completed tasks are marked, a prefix and a style that can be combined */
+#[derive(Clone)]
+struct CompletedStyle {
+    prefix: Option<String>,
+    style: Style
+}
+
+
+impl Default for CompletedStyle {
+    // strikethrough only, how completed tasks have always looked
+    fn default() -> Self {
+        return CompletedStyle { prefix: None, style: Style::from(Effect::Strikethrough) };
+    }
+}
+
+
+/** Used for storing which tasks the list shows, a filter left empty shows every task */
+#[derive(Clone, Default)]
+struct Filter {
+    // only show tasks changed within this many days
+    recent_days: Option<usize>,
+    // only show open tasks created more than this many days ago
+    stale_days: Option<usize>,
+    // only show tasks created a certain way, see SOURCE_MANUAL
+    source: Option<String>,
+    // someday tasks are kept out of the list, when true only they are shown instead
+    someday: bool,
+    effort_sort: EffortSort,
+    // completed tasks are shown under one header row at the bottom instead of between the open ones
+    group_completed: bool,
+    completed_collapsed: bool
+}
+
+
+/** Used for storing whether the list is sorted by effort instead of the order tasks were dragged into */
+#[derive(Clone, Copy, Default, PartialEq)]
+enum EffortSort {
+    #[default]
+    Manual,
+    EasiestFirst,
+    HardestFirst
+}
+
+
+/** How a task was created, stored in the source column when it is inserted. Tasks from before sources were tracked show as unknown */
+const SOURCE_MANUAL: &str = "manual";
+const SOURCE_CAPTURE: &str = "capture";
+const SOURCE_DEMO: &str = "demo";
+
+
+/** Value of the header row completed tasks are grouped under. It is a control character, which sanitize_name keeps out of every
+ * task name, so not even a task with an empty name left by an older version is mistaken for the header
+ */
+const GROUP_HEADER: &str = "\u{0}";
+
+
+/** Width of the task list in columns unless the list_width setting changes it, popups listing tasks always use it */
+const LIST_WIDTH: usize = 35;
+
+
+/** How many rows are numbered when the number_keys setting is on, one for every number key */
+const NUMBERED_ROWS: usize = 9;
+
+
+/** Narrowest the list_width setting can make the list */
+const MIN_LIST_WIDTH: usize = 10;
+
+
+/** Columns taken by the borders and padding of the two dialogs around the list plus its scrollbar */
+const LIST_CHROME: usize = 10;
+
+
+/** Pause after which typing to jump starts over from a new prefix */
+const TYPE_AHEAD_PAUSE: time::Duration = time::Duration::from_secs(1);
+
+
+/** Used for reporting the result of renaming a task */
+enum RenameOutcome {
+    Renamed,
+    NotFound,
+    // another task already has the new name, names have to stay unique
+    NameTaken
+}
+
+
+/** This code is for a CLI to-do list built entirely in Rust with a functioning sqlite database locally on a machine.
+ * The CLI was built using the Cursive crate to build views, and rusqlite was used for database operations.
+ * Purpose of this project was to become familiar with Rust's crate ecosystem.
+ */
+fn main() {
+    // inspect mode opens the list for browsing only, nothing can be added, deleted or toggled
+    let options = cli::parse_args(std::env::args().skip(1));
+    let inspect = options.inspect;
+    // a headless command runs against the database without opening the list, failures are reported through the exit code
+    if !options.command.is_empty() {
+        // inspecting only allows commands that read, and opens the database the same way the list would
+        let conn = match inspect {
+            true => cli::check_inspect(&options.command).and_then(|_| open_inspect(&options.db_path).map_err(cli::CliError::Io)),
+            false => open_database(&options.db_path).map_err(cli::CliError::from)
+        };
+        let result = conn.and_then(|conn| cli::run(&conn, &options.command));
+        match result {
+            // quiet commands like capture have nothing to print
+            Ok(message) if message.is_empty() => {}
+            Ok(message) => println!("{message}"),
+            Err(error) => {
+                eprintln!("{error}");
+                std::process::exit(error.exit_code());
+            }
+        }
+        return;
+    }
+    // logs are kept in memory and shown in the debug console, cursive's own logs are only kept when something goes wrong.
+    // started before the database is opened so problems found while migrating it are kept too
+    cursive::logger::init();
+    cursive::logger::set_internal_filter_level(log::LevelFilter::Warn);
+    // connection and path of database, connection is needed for database creationa & transactions
+    let mut conn = if inspect {
+        open_inspect(&options.db_path).unwrap_or_else(|problem| {
+            eprintln!("{problem}");
+            std::process::exit(1);
+        })
+    }
+    else {
+        open_database(&options.db_path).expect("Failed to open the database")
+    };
+    // settings that can't be used fall back to their defaults and are reported once the list is open
+    let mut setting_problems = settings::check(&conn);
+
+    // main cursive instance
+    let mut siv = cursive::default();
+    setting_problems.extend(bind_keys(&mut siv, &conn));
+    // Old completed tasks are moved out of the list on startup when auto archiving is enabled
+    let archive_days = settings::get_usize(&conn, settings::AUTO_ARCHIVE_DAYS, 0);
+    if archive_days > 0 && !inspect {
+        let archived = auto_archive(&mut conn, archive_days).expect("Error archiving old tasks");
+        log::info!("Archived {archived} tasks completed more than {archive_days} days ago");
+    }
+    // sample tasks are only added to an empty list, so a real list is never mixed with them
+    let demo_message = match options.demo {
+        false => None,
+        true if inspect => Some("--demo can't add sample tasks in inspect mode".to_string()),
+        true => match seed_demo(&mut conn).expect("Error adding sample tasks") {
+            0 => Some("The list already has tasks, --demo only fills an empty list".to_string()),
+            added => Some(format!("Added {added} sample tasks, remove them with the clear-demo command"))
+        }
+    };
+    // Huge lists are slow to build into a view, so warn and offer to only show part of them
+    let task_count = count_tasks(&conn);
+    let threshold = settings::get_usize(&conn, settings::LARGE_LIST_THRESHOLD, 1000);
+    // the setup connection is swapped for a read only one that lives as long as the list, see open_write
+    drop(conn);
+    let conn = open_read_only(&options.db_path);
+    siv.set_user_data(app_data(conn, options.db_path, inspect, options.recent_days));
+    // long labels are cut to fit the terminal, so they have to be cut again when it changes size
+    siv.add_global_callback(Event::WindowResize, refit_labels);
+
+    if threshold > 0 && task_count > threshold {
+        siv.add_layer(Dialog::text(format!("This list has {task_count} tasks, showing all of them may be slow.\nOnly show the first {threshold}?"))
+            .title("Large list")
+            .button("Limit view", move |s| {
+                s.pop_layer();
+                show_list(s, Some(threshold));
+            })
+            .button("Show all", |s| {
+                s.pop_layer();
+                show_list(s, None);
+            }));
+    }
+    else {
+        show_list(&mut siv, None);
+    }
+    if !setting_problems.is_empty() {
+        for problem in &setting_problems {
+            log::warn!("{problem}");
+        }
+        siv.add_layer(Dialog::info(format!("Some settings were ignored:\n{}", setting_problems.join("\n"))).title("Settings"));
+    }
+    if let Some(message) = demo_message {
+        siv.add_layer(Dialog::info(message).title("Demo"));
+    }
+    quit_on_signal(&siv);
+    siv.run();
+}
+
+
+/** Used for binding the key of every action in keybindings::ACTIONS and the number keys, returns every binding that can't be used */
+fn bind_keys(siv: &mut Cursive, conn: &Connection) -> Vec<String> {
+    let (bindings, mut problems) = keybindings::load(conn);
+    for (action, event) in &bindings {
+        siv.add_global_callback(event.clone(), action_callback(action));
+    }
+    // number keys toggle the numbered rows, a number that is already bound keeps its action
+    if settings::get_bool(conn, settings::NUMBER_KEYS, false) {
+        for row in 1..=NUMBERED_ROWS {
+            let key = Event::Char(char::from_digit(row as u32, 10).unwrap());
+            if let Some((action, _)) = bindings.iter().find(|(_, bound)| *bound == key) {
+                problems.push(format!("number_keys: {row} is bound to key.{action}, it doesn't toggle row {row}"));
+                continue;
+            }
+            siv.add_global_callback(key, move |s| toggle_row(s, row));
+        }
+    }
+    return problems;
+}
+
+
+/** Used for building the state shared by the callbacks of the list, labels and filters start out the way the settings have them */
+fn app_data(conn: Connection, db_path: String, inspect: bool, recent_days: Option<usize>) -> AppData {
+    let filter = Filter {
+        recent_days,
+        group_completed: settings::get_bool(&conn, settings::GROUP_COMPLETED, false),
+        completed_collapsed: settings::get_bool(&conn, settings::COMPLETED_COLLAPSED, false),
+        ..Filter::default()
+    };
+    return AppData { label_width: available_label_width(&conn), labels: load_labels(&conn), conn, db_path, inspect, add_another: false,
+        limit: None, filter, type_ahead: Arc::new(Mutex::new(TypeAhead::default())) };
+}
+
+
+/** Used for quitting through the normal exit path when the process is interrupted or its terminal is closed, so the terminal is restored
+ * and the database connection is closed instead of the process dying mid-frame. Every change is already committed on its own short-lived
+ * connection, see open_write, so nothing is left to write. Quitting is handed to the ui thread through the callback sink, the signal
+ * thread never touches the connection itself, so it can't deadlock with a write in progress.
+ */
+fn quit_on_signal(siv: &Cursive) {
+    let cb_sink = siv.cb_sink().clone();
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP]).expect("Failed to install signal handlers");
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            log::info!("Received signal {signal}, quitting");
+            // the ui has already shut down when the sink is gone
+            if cb_sink.send(Box::new(|s| s.quit())).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+
+/** Used for looking up what a key bound in keybindings::ACTIONS does */
+fn action_callback(action: &str) -> fn(&mut Cursive) {
+    return match action {
+        "quit" => |s| s.quit(),
+        "debug_console" => |s| s.toggle_debug_console(),
+        "toggle_ids" => toggle_ids,
+        "dismiss_greeting" => |s| {
+            s.call_on_name("greeting", |view: &mut HideableView<TextView>| view.hide());
+        },
+        "add" => add_todo,
+        "edit" => edit_todo,
+        "delete" => remove_todo,
+        "block" => set_blocker,
+        "reset" => reset_todo,
+        "toggle_recent" => toggle_recent,
+        "filter_source" => filter_source,
+        "details" => show_details,
+        "someday" => toggle_someday,
+        "someday_view" => toggle_someday_view,
+        "type_ahead" => start_type_ahead,
+        "sort_effort" => sort_by_effort,
+        "export_html" => export_html,
+        "toggle_stale" => toggle_stale,
+        "review" => start_review,
+        "replace" => search_replace,
+        "toggle_status" => |s| {
+            if let Some(task) = s.find_name::<SelectView<String>>("tasks").and_then(|tasks| selected_task(&tasks)) {
+                set_status(s, &task);
+            }
+        },
+        _ => unreachable!("no callback for key binding action {action}")
+    };
+}
+
+
+/** Used for storing everything the main list view is built from, read from the database when the list is shown */
+struct ListData {
+    // styled label and plain name of every shown task, along with how many tasks couldn't be read
+    task_list: Vec<(StyledString, String)>,
+    skipped: usize,
+    task_count: usize,
+    inspect: bool,
+    label_width: usize,
+    completed_today: usize,
+    // description of the active filter shown under the list
+    status: String,
+    type_ahead_state: Arc<Mutex<TypeAhead>>,
+    // what Enter does on a task
+    on_submit: fn(&mut Cursive, &str),
+    // None when the list takes the full width of the terminal
+    width: Option<usize>
+}
+
+
+/** Used for reading everything the main list view is built from, limit restricts how many tasks are loaded for very large lists */
+fn list_data(app: &mut AppData, limit: Option<usize>) -> ListData {
+    app.limit = limit;
+    let (task_list, skipped) = retrieve_list(&app.conn, limit, &app.labels, &app.filter);
+    return ListData {
+        task_list,
+        skipped,
+        task_count: count_tasks(&app.conn),
+        inspect: app.inspect,
+        label_width: app.label_width,
+        completed_today: count_completed_today(&app.conn),
+        status: filter_status(&app.filter),
+        type_ahead_state: app.type_ahead.clone(),
+        on_submit: enter_action(&app.conn),
+        width: list_width(&app.conn)
+    };
+}
+
+
+/** Used for showing the main list, a short progress bar fills up before it appears */
+fn show_list(s: &mut Cursive, limit: Option<usize>) {
+    // Retrieving data as vector to add into view
+    let mut data = s.with_user_data(|app: &mut AppData| list_data(app, limit));
+    let start = time::Instant::now();
+    let async_view = AsyncProgressView::new(s, move || {
+        if start.elapsed().as_secs() < 5 {
+            AsyncProgressState::Pending(start.elapsed().as_secs_f32() / 5f32)
+        } 
+        else {
+            // the view is only asked for until it is available, so it is built once
+            AsyncProgressState::Available(list_view(data.take().expect("Failed to access database"), limit))
+        }
+    });
+    s.add_layer(Dialog::around(async_view).title("Rusty To-Do List"));
+}
+
+
+/** Used for building the main list view out of what list_data read */
+fn list_view(data: ListData, limit: Option<usize>) -> Dialog {
+    let ListData { task_list, skipped, task_count, inspect, label_width, completed_today, status, type_ahead_state, on_submit, width } = data;
+    // Creating view to populate with fetched data of tasks, plain text is data used for database operations, styled task is how its presented visually
+    let mut tasks_view = SelectView::<String>::new();
+    let shown = task_list.len();
+    for (styled_task, plain_task) in task_list {
+        tasks_view.add_item(truncate_label(&styled_task, label_width), plain_task);
+    }
+
+    let tasks = type_ahead(draggable(tasks_view
+        .on_submit(move |s, name: &String| {
+            if name == GROUP_HEADER {
+                toggle_completed_group(s);
+            }
+            else {
+                on_submit(s, name);
+            }
+        })
+        .with_name("tasks")), type_ahead_state)
+        .scrollable()
+        // a full width list follows the terminal when it is resized
+        .resized(width.map_or(SizeConstraint::Full, SizeConstraint::Fixed), SizeConstraint::Fixed(12));
+
+    // mutating buttons are greyed out while inspecting
+    let buttons = LinearLayout::horizontal()
+        .child(Button::new("Add", add_todo).with_enabled(!inspect))
+        .child(Button::new("Edit", edit_todo).with_enabled(!inspect))
+        .child(Button::new("Delete", remove_todo).with_enabled(!inspect))
+        .child(Button::new("Block", set_blocker).with_enabled(!inspect))
+        .child(Button::new("Reset", reset_todo).with_enabled(!inspect));
+    let mut layout = LinearLayout::vertical();
+    if inspect {
+        layout.add_child(TextView::new("INSPECT MODE - read only").center());
+    }
+    layout.add_child(tasks);
+    layout.add_child(TextView::new(status).with_name("filter_status"));
+    layout.add_child(TextView::new("").with_name("type_ahead"));
+    if skipped > 0 {
+        let (plural, verb) = if skipped == 1 { ("task", "was") } else { ("tasks", "were") };
+        layout.add_child(TextView::new(format!("{skipped} {plural} couldn't be read and {verb} skipped, see the debug console (~)"))
+            .style(ColorStyle::secondary()));
+    }
+    if limit.is_some_and(|limit| task_count > limit) {
+        layout.add_child(TextView::new(format!("Showing {shown} of {task_count} tasks")));
+    }
+    // a small greeting when tasks were already finished today, Esc hides it
+    if completed_today > 0 {
+        let plural = if completed_today == 1 { "task" } else { "tasks" };
+        layout.add_child(HideableView::new(TextView::new(format!("Welcome back! You completed {completed_today} {plural} today.")))
+            .with_name("greeting"));
+    }
+    return Dialog::around(layout
+        .child(buttons));
+}
+
+
+/** Used for picking what Enter does on a task, toggling its status unless the enter_action setting asks for its details */
+fn enter_action(conn: &Connection) -> fn(&mut Cursive, &str) {
+    return match settings::get(conn, settings::ENTER_ACTION).as_deref().map(str::trim) {
+        None | Some("toggle") => set_status,
+        Some("details") => |s, _| show_details(s),
+        Some(other) => {
+            log::warn!("Unknown enter_action '{other}', Enter toggles the task status");
+            set_status
+        }
+    };
+}
+
+
+/** How long a write waits for a read of the open list or another write to finish before giving up */
+const BUSY_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+
+/** Used for opening the task database and bringing it up to the current schema */
+fn open_database(db_path: &str) -> Result<Connection> {
+    let mut conn = Connection::open(db_path)?;
+    // headless commands can run while the list is reading, so they wait for it like open_write does
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    create_table(&conn)?;
+    migrate(&mut conn)?;
+    // needed so deleting a task unblocks the tasks that were blocked by it
+    conn.pragma_update(None, "foreign_keys", true)?;
+    settings::create_table(&conn)?;
+    return Ok(conn);
+}
+
+
+/** Used for opening the database for inspect mode, read only and without bringing it up to the current schema so the file is
+ * never changed. A file that doesn't exist yet or was created by an older version can't be shown as it is, so it is refused instead
+ */
+fn open_inspect(db_path: &str) -> std::result::Result<Connection, String> {
+
+    // Nested function for retrieving the columns of a table, empty when the table doesn't exist
+    fn columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+        return conn.prepare("SELECT name FROM pragma_table_info(?1) ORDER BY name")?
+            .query_map([table], |row| row.get(0))?
+            .collect();
+    }
+
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+        .map_err(|error| format!("Can't inspect '{db_path}': {error}"))?;
+    // the current schema is built in memory to compare against, so it can't fall behind create_table
+    let current = Connection::open_in_memory().and_then(|memory| {
+        create_table(&memory)?;
+        settings::create_table(&memory)?;
+        return Ok(memory);
+    }).expect("Error building the current schema");
+    for table in ["tasks", "archived_tasks", "settings"] {
+        let missing = columns(&current, table)
+            .and_then(|expected| columns(&conn, table).map(|found| expected.iter().any(|column| !found.contains(column))))
+            .map_err(|error| format!("Can't inspect '{db_path}': {error}"))?;
+        if missing {
+            return Err(format!("'{db_path}' was created by an older version, open it once without --inspect to upgrade it"));
+        }
+    }
+    return Ok(conn);
+}
+
+
+/** Used for opening the long-lived connection the list reads through, it never takes a write lock */
+fn open_read_only(db_path: &str) -> Connection {
+    return Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+        .expect("Failed to open the database");
+}
+
+
+/** Used for opening a short-lived connection for a single mutation, it is closed again when dropped.
+ * The list can stay open for a long time, so instead of holding a writable connection the whole time it reads through a read only
+ * connection and only opens one of these when something changes. This keeps write locks short so headless commands run
+ * against the same database at the same time don't have to fight the open list for them.
+ */
+fn open_write(db_path: &str) -> Connection {
+    let conn = Connection::open(db_path).expect("Failed to open the database for writing");
+    // wait for a concurrent writer to finish instead of failing right away
+    conn.busy_timeout(BUSY_TIMEOUT).expect("Error setting busy timeout");
+    conn.pragma_update(None, "foreign_keys", true).expect("Error enabling foreign keys");
+    return conn;
+}
+
+
+/** Used for creating the database of tasks for the todo list */
+fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tasks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            completed BOOLEAN,
+            blocked_by INTEGER REFERENCES tasks(id) ON DELETE SET NULL,
+            completed_at TEXT,
+            remote_id TEXT,
+            synced_at TEXT,
+            updated_at TEXT,
+            sort_order INTEGER,
+            source TEXT,
+            someday BOOLEAN NOT NULL DEFAULT FALSE,
+            effort INTEGER CHECK (effort BETWEEN 1 AND 5),
+            created_at TEXT
+    )", [])?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS archived_tasks (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            completed BOOLEAN,
+            completed_at TEXT,
+            archived_at TEXT,
+            remote_id TEXT,
+            synced_at TEXT,
+            source TEXT,
+            effort INTEGER,
+            created_at TEXT
+    )", [])?;
+    return Ok(());
+}
+
+
+/** Used for bringing databases created by older versions up to the current tasks schema */
+fn migrate(conn: &mut Connection) -> Result<()> {
+    // tasks used to be keyed by their name, rebuild the table so every task gets a stable integer id other tasks can reference
+    if !has_column(conn, "tasks", "id")? {
+        let tx = conn.transaction()?;
+        tx.execute_batch(
+            "CREATE TABLE tasks_new (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                completed BOOLEAN,
+                blocked_by INTEGER REFERENCES tasks(id) ON DELETE SET NULL
+            );
+            INSERT INTO tasks_new (name, completed) SELECT name, completed FROM tasks
+                WHERE rowid IN (SELECT MIN(rowid) FROM tasks WHERE name IS NOT NULL GROUP BY name) ORDER BY rowid;")?;
+        // rows whose name is missing or taken by an earlier row can't get an id of their own. Instead of failing startup they are
+        // kept aside in their own table, where they can be looked at and restored by hand
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS quarantined_tasks (
+                name TEXT,
+                completed BOOLEAN,
+                reason TEXT,
+                quarantined_at TEXT
+            );
+            INSERT INTO quarantined_tasks (name, completed, reason, quarantined_at)
+                SELECT name, completed, CASE WHEN name IS NULL THEN 'missing name' ELSE 'duplicate name' END, datetime('now') FROM tasks
+                WHERE rowid NOT IN (SELECT MIN(rowid) FROM tasks WHERE name IS NOT NULL GROUP BY name);")?;
+        let quarantined = tx.changes();
+        if quarantined > 0 {
+            log::warn!("Moved {quarantined} tasks with a missing or duplicate name to the quarantined_tasks table while giving tasks ids");
+        }
+        tx.execute_batch(
+            "DROP TABLE tasks;
+            ALTER TABLE tasks_new RENAME TO tasks;")?;
+        tx.commit()?;
+    }
+    if !has_column(conn, "tasks", "completed_at")? {
+        conn.execute("ALTER TABLE tasks ADD COLUMN completed_at TEXT", [])?;
+    }
+    if !has_column(conn, "tasks", "remote_id")? {
+        conn.execute_batch("ALTER TABLE tasks ADD COLUMN remote_id TEXT; ALTER TABLE tasks ADD COLUMN synced_at TEXT;")?;
+    }
+    if !has_column(conn, "tasks", "updated_at")? {
+        conn.execute("ALTER TABLE tasks ADD COLUMN updated_at TEXT", [])?;
+    }
+    // updated_at is kept by triggers so every change counts, including ones made by headless commands
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS tasks_inserted AFTER INSERT ON tasks BEGIN
+            UPDATE tasks SET updated_at = datetime('now') WHERE id = NEW.id;
+        END;")?;
+    if !has_column(conn, "tasks", "sort_order")? {
+        conn.execute_batch("ALTER TABLE tasks ADD COLUMN sort_order INTEGER; UPDATE tasks SET sort_order = id;")?;
+    }
+    if !has_column(conn, "tasks", "source")? {
+        conn.execute("ALTER TABLE tasks ADD COLUMN source TEXT", [])?;
+    }
+    if !has_column(conn, "tasks", "someday")? {
+        conn.execute("ALTER TABLE tasks ADD COLUMN someday BOOLEAN NOT NULL DEFAULT FALSE", [])?;
+    }
+    // how hard a task feels from 1 to 5, unrated tasks have none
+    if !has_column(conn, "tasks", "effort")? {
+        conn.execute("ALTER TABLE tasks ADD COLUMN effort INTEGER CHECK (effort BETWEEN 1 AND 5)", [])?;
+    }
+    // creation times of older tasks weren't kept, the last change is the closest known time
+    if !has_column(conn, "tasks", "created_at")? {
+        conn.execute_batch("ALTER TABLE tasks ADD COLUMN created_at TEXT; UPDATE tasks SET created_at = updated_at;")?;
+    }
+    // the update trigger is recreated so databases made before effort and someday existed count changing them too
+    conn.execute_batch(
+        "DROP TRIGGER IF EXISTS tasks_updated;
+        CREATE TRIGGER tasks_updated AFTER UPDATE OF name, completed, blocked_by, effort, someday ON tasks BEGIN
+            UPDATE tasks SET updated_at = datetime('now') WHERE id = NEW.id;
+        END;")?;
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS tasks_created_at AFTER INSERT ON tasks WHEN NEW.created_at IS NULL BEGIN
+            UPDATE tasks SET created_at = datetime('now') WHERE id = NEW.id;
+        END;")?;
+    // new tasks go to the bottom of the list
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS tasks_sort_order AFTER INSERT ON tasks WHEN NEW.sort_order IS NULL BEGIN
+            UPDATE tasks SET sort_order = (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM tasks) WHERE id = NEW.id;
+        END;")?;
+    // archived tasks used to lose everything but their name and completion, newer databases keep what a task knows about itself
+    for (column, kind) in [("remote_id", "TEXT"), ("synced_at", "TEXT"), ("source", "TEXT"), ("effort", "INTEGER"), ("created_at", "TEXT")] {
+        if !has_column(conn, "archived_tasks", column)? {
+            conn.execute(&format!("ALTER TABLE archived_tasks ADD COLUMN {column} {kind}"), [])?;
+        }
+    }
+    // a remote id points at exactly one task
+    conn.execute("CREATE UNIQUE INDEX IF NOT EXISTS tasks_remote_id ON tasks (remote_id)", [])?;
+    return Ok(());
+}
+
+
+/** Used for moving tasks completed more than the given number of days ago into the archive, returns how many were moved */
+fn auto_archive(conn: &mut Connection, days: usize) -> Result<usize> {
+    let cutoff = format!("-{days} days");
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO archived_tasks (id, name, completed, completed_at, archived_at, remote_id, synced_at, source, effort, created_at)
+        SELECT id, name, completed, completed_at, datetime('now'), remote_id, synced_at, source, effort, created_at
+        FROM tasks WHERE completed AND completed_at < datetime('now', ?1)",
+        [&cutoff])?;
+    let archived = tx.execute("DELETE FROM tasks WHERE completed AND completed_at < datetime('now', ?1)", [&cutoff])?;
+    tx.commit()?;
+    return Ok(archived);
+}
+
+
+/** Used for checking whether a table already has a column, migrations use this to know what is missing */
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")?;
+    return stmt.exists([table, column]);
+}
+
+
+/** Sample task of the demo list: name, days since completion if completed, effort, someday and days since creation */
+type DemoTask = (&'static str, Option<i64>, Option<i64>, bool, i64);
+
+/** Sample tasks --demo fills an empty list with */
+const DEMO_TASKS: [DemoTask; 10] = [
+    ("Buy groceries", None, Some(1), false, 0),
+    ("Pick a date for the team dinner", Some(1), Some(2), false, 6),
+    ("Book a table for the team dinner", None, Some(2), false, 5),
+    ("Renew passport", None, Some(3), false, 45),
+    ("Fix the leaking kitchen tap", None, Some(4), false, 12),
+    ("Water the plants", Some(0), Some(1), false, 3),
+    ("Write the quarterly report", None, Some(5), false, 2),
+    ("Send the draft report for review", None, Some(1), false, 2),
+    ("Learn to play the guitar", None, Some(5), true, 90),
+    ("Call the dentist", Some(3), None, false, 10)
+];
+
+/** Blocked tasks of the demo list along with the task blocking them */
+const DEMO_BLOCKERS: [(&str, &str); 2] = [
+    ("Book a table for the team dinner", "Pick a date for the team dinner"),
+    ("Send the draft report for review", "Write the quarterly report")
+];
+
+
+/** Used for filling an empty list with sample tasks for trying the list out, returns how many were added.
+ * A list that already has tasks is left alone, the samples all have the demo source so clear-demo can remove them again
+ */
+fn seed_demo(conn: &mut Connection) -> Result<usize> {
+    if count_tasks(conn) > 0 {
+        return Ok(0);
+    }
+    let tx = conn.transaction()?;
+    for (name, completed_days, effort, someday, created_days) in DEMO_TASKS {
+        tx.execute(
+            "INSERT INTO tasks (name, completed, completed_at, effort, someday, source, created_at)
+            VALUES (?1, ?2, datetime('now', ?3), ?4, ?5, ?6, datetime('now', ?7))",
+            params![name, completed_days.is_some(), completed_days.map(|days| format!("-{days} days")), effort, someday, SOURCE_DEMO,
+                format!("-{created_days} days")])?;
+    }
+    for (task, blocker) in DEMO_BLOCKERS {
+        tx.execute("UPDATE tasks SET blocked_by = (SELECT id FROM tasks WHERE name = ?2) WHERE name = ?1", [task, blocker])?;
+    }
+    tx.commit()?;
+    return Ok(DEMO_TASKS.len());
+}
+
+
+/** Used for counting every task in the database */
+fn count_tasks(conn: &Connection) -> usize {
+    return conn.query_row("SELECT COUNT(*) FROM tasks", [], |row| row.get(0)).expect("Error counting tasks");
+}
+
+
+/** Used for counting the tasks completed today in local time, tasks without a completion time are not counted */
+fn count_completed_today(conn: &Connection) -> usize {
+    return conn.query_row(
+        "SELECT COUNT(*) FROM tasks WHERE completed AND date(completed_at, 'localtime') = date('now', 'localtime')",
+        [], |row| row.get(0)).expect("Error counting completed tasks");
+}
+
+
+/** Used for blocking mutating actions while in inspect mode, returns true and informs the user if the action was blocked */
+fn inspect_guard(s: &mut Cursive) -> bool {
+    let inspect = s.with_user_data(|app: &mut AppData| app.inspect).unwrap_or(false);
+    if inspect {
+        show_info(s, "Inspect mode is on, changes are disabled");
+    }
+    return inspect;
+}
+
+
+/** Used for showing a message, the same message isn't stacked again while it is still open so pressing a key repeatedly shows it once */
+fn show_info(s: &mut Cursive, message: impl Into<String>) {
+    let message = message.into();
+    open_dialog(s, &format!("info: {message}"), Dialog::info(message));
+}
+
+
+/** Used for opening a dialog unless one of the same kind is already open, so pressing a key or button repeatedly doesn't stack copies.
+ * The dialog is found again by its kind, so closing it any way frees the kind again
+ */
+fn open_dialog(s: &mut Cursive, kind: &str, dialog: Dialog) {
+    if s.find_name::<Dialog>(kind).is_some() {
+        return;
+    }
+    s.add_layer(dialog.with_name(kind));
+}
+
+
+/** Used for selecting tasks along with whether their blocker is still unfinished, shared by every query building a Task */
+const TASK_QUERY: &str = "SELECT t.name, t.completed, COALESCE(b.completed = 0, FALSE), t.id, t.remote_id, t.synced_at,
+    CAST(julianday('now') - julianday(t.created_at) AS INTEGER) AS age_days FROM tasks t LEFT JOIN tasks b ON b.id = t.blocked_by";
+
+
+/** Used for turning a row selected with TASK_QUERY into a Task */
+fn task_from_row(row: &rusqlite::Row) -> Result<Task> {
+    Ok(Task {
+        // task name is tied to column 0, completion state is tied to column 1, blocked state is tied to column 2, id is tied to column 3,
+        // remote id and sync time are tied to columns 4 and 5, age is tied to column 6
+        id: row.get(3)?,
+        name: row.get(0)?,
+        completed: row.get(1)?,
+        blocked: row.get(2)?,
+        remote_id: row.get(4)?,
+        synced_at: row.get(5)?,
+        age_days: row.get(6)?
+    })
+}
+
+
+/** Used for building how a task is presented in the list, styled by the completed marker when completed and marked when blocked
+ * or stale, optionally prefixed with its id */
+fn task_label(task: &Task, options: &LabelOptions) -> StyledString {
+    let mut label = StyledString::new();
+    if options.show_ids {
+        label.append_styled(format!("#{} ", task.id), ColorStyle::secondary());
+    }
+    if task.blocked {
+        label.append_styled("[blocked] ", ColorStyle::secondary());
+    }
+    let stale = options.stale_days > 0 && task.age_days.is_some_and(|age| age > options.stale_days as i64);
+    if !task.completed && stale {
+        // a long neglected task is greyed out behind a cobweb
+        label.append_styled("🕸 ", ColorStyle::secondary());
+        label.append_styled(task.name.clone(), ColorStyle::secondary());
+    }
+    else if !task.completed {
+        label.append_styled(task.name.clone(), Effect::Simple);
+    }
+    else {
+        if let Some(prefix) = &options.completed.prefix {
+            label.append_styled(format!("{prefix} "), options.completed.style);
+        }
+        label.append_styled(task.name.clone(), options.completed.style);
+    }
+    return label;
+}
+
+
+/** Used for parsing the completed_style setting, a comma separated combination of an optional `prefix:<text>`, effects like
+ * `strikethrough` or `bold` and an optional `color:<color>`. Returns an error describing the first conflicting or unknown part
+ */
+fn parse_completed_style(value: &str) -> std::result::Result<CompletedStyle, String> {
+    let mut prefix: Option<String> = None;
+    let mut color: Option<Color> = None;
+    let mut effects: Vec<Effect> = Vec::new();
+    for part in value.split(',').map(|part| part.trim()).filter(|part| !part.is_empty()) {
+        if let Some(text) = part.strip_prefix("prefix:") {
+            if prefix.replace(text.trim().to_string()).is_some() {
+                return Err("more than one prefix".to_string());
+            }
+        }
+        else if let Some(name) = part.strip_prefix("color:") {
+            let parsed = Color::parse(name.trim()).ok_or(format!("unknown color '{}'", name.trim()))?;
+            if color.replace(parsed).is_some() {
+                return Err("more than one color".to_string());
+            }
+        }
+        else {
+            let effect: Effect = part.parse().map_err(|_| format!("unknown effect '{part}'"))?;
+            effects.push(effect);
+        }
+    }
+    // simple means no effect at all, so it can't be combined with one
+    if effects.contains(&Effect::Simple) && effects.len() > 1 {
+        return Err("simple can't be combined with other effects".to_string());
+    }
+    // completed tasks have to look different from open ones
+    if prefix.is_none() && color.is_none() && effects.iter().all(|effect| *effect == Effect::Simple) {
+        return Err("completed tasks would look like open tasks".to_string());
+    }
+    let mut style = Style::merge(&effects.into_iter().map(Style::from).collect::<Vec<Style>>());
+    if let Some(color) = color {
+        style = style.combine(color);
+    }
+    return Ok(CompletedStyle { prefix, style });
+}
+
+
+/** Used for loading how task labels are built from the settings */
+fn load_labels(conn: &Connection) -> LabelOptions {
+    return LabelOptions {
+        show_ids: settings::get_bool(conn, settings::SHOW_IDS, false),
+        completed: load_completed_style(conn),
+        numbered: settings::get_bool(conn, settings::NUMBER_KEYS, false),
+        stale_days: settings::get_usize(conn, settings::STALE_DAYS, 30)
+    };
+}
+
+
+/** Used for loading the completed marker, falls back to strikethrough only when the setting can't be used, settings::check reports why */
+fn load_completed_style(conn: &Connection) -> CompletedStyle {
+    return settings::get(conn, settings::COMPLETED_STYLE)
+        .and_then(|value| parse_completed_style(&value).ok())
+        .unwrap_or_default();
+}
+
+
+/** Used for retrieving todo list data to be displayed in the cursive view, each styled label is paired with the plain task name */
+fn retrieve_list(conn: &Connection, limit: Option<usize>, labels: &LabelOptions, filter: &Filter) -> (Vec<(StyledString, String)>, usize) {
+    let mut result_vec: Vec<(StyledString, String)> = Vec::new();
+    // every filter that is left empty binds NULL and matches every task
+    let mut stmt = conn.prepare(&format!("{TASK_QUERY}
+        WHERE (?2 IS NULL OR t.updated_at >= datetime('now', ?2))
+        AND (?3 IS NULL OR COALESCE(t.source, 'unknown') = ?3)
+        AND t.someday = ?4
+        AND (?6 IS NULL OR (NOT t.completed AND age_days > ?6))
+        ORDER BY CASE ?5 WHEN 1 THEN COALESCE(t.effort, 6) WHEN -1 THEN -COALESCE(t.effort, 0) ELSE 0 END, t.sort_order, t.id
+        LIMIT ?1")).expect("Error retrieving tasks from database");
+
+    // a negative limit means no limit in sqlite
+    let limit = limit.map_or(-1, |limit| limit as i64);
+    let recent_cutoff = filter.recent_days.map(|days| format!("-{days} days"));
+    // unrated tasks go last in both effort orders
+    let effort_order = match filter.effort_sort {
+        EffortSort::Manual => 0,
+        EffortSort::EasiestFirst => 1,
+        EffortSort::HardestFirst => -1
+    };
+    // stale tasks are found by their whole days of age like the marker of task_label, so the filter shows exactly the marked tasks
+    let stale_days = filter.stale_days.map(|days| days as i64);
+    let task_iter = stmt.query_map(params![limit, recent_cutoff, filter.source, filter.someday, effort_order, stale_days], task_from_row);
+
+    let mut completed: Vec<Task> = Vec::new();
+    // a row that can't be read, e.g. one edited by hand, is skipped and counted instead of failing the whole list
+    let mut skipped = 0;
+    for task in task_iter.expect("Failed to query tasks") {
+        let unwrapped_task = match task {
+            Ok(task) => task,
+            Err(error @ (rusqlite::Error::FromSqlConversionFailure(..) | rusqlite::Error::InvalidColumnType(..)
+                | rusqlite::Error::IntegralValueOutOfRange(..))) => {
+                log::warn!("Skipping a task that can't be read: {error}");
+                skipped += 1;
+                continue;
+            }
+            // anything else is a problem with the database itself, reading on would only fail again
+            Err(error) => {
+                log::warn!("Stopped reading tasks: {error}");
+                skipped += 1;
+                break;
+            }
+        };
+        if filter.group_completed && unwrapped_task.completed {
+            completed.push(unwrapped_task);
+            continue;
+        }
+        result_vec.push((task_label(&unwrapped_task, labels), unwrapped_task.name));
+    }
+    // grouped completed tasks follow a header row that folds them away
+    if !completed.is_empty() {
+        let arrow = if filter.completed_collapsed { "▸" } else { "▾" };
+        result_vec.push((StyledString::styled(format!("{arrow} Completed ({})", completed.len()), Effect::Bold), GROUP_HEADER.to_string()));
+        if !filter.completed_collapsed {
+            for task in completed {
+                result_vec.push((task_label(&task, labels), task.name));
+            }
+        }
+    }
+    if labels.numbered {
+        for (row, (label, _)) in result_vec.iter_mut().filter(|(_, name)| name != GROUP_HEADER).take(NUMBERED_ROWS).enumerate() {
+            *label = number_label(label, row + 1);
+        }
+    }
+    return (result_vec, skipped);
+}
+
+
+/** Used for retrieving the name of the selected task, None when nothing or the completed group header is selected */
+fn selected_task(tasks: &SelectView<String>) -> Option<String> {
+    return tasks.selection().map(|name| name.to_string()).filter(|name| name != GROUP_HEADER);
+}
+
+
+/** Used for folding or unfolding the completed group, the choice is saved unless inspecting */
+fn toggle_completed_group(s: &mut Cursive) {
+    s.with_user_data(|app: &mut AppData| {
+        app.filter.completed_collapsed = !app.filter.completed_collapsed;
+        if !app.inspect {
+            settings::set(&open_write(&app.db_path), settings::COMPLETED_COLLAPSED, &app.filter.completed_collapsed.to_string());
+        }
+    });
+    reload_list(s);
+}
+
+
+/** Used for rebuilding the list after a task was added, removed, moved or changed status while rows depend on their position,
+ * so a task moves in or out of the completed group and the row numbers stay in order
+ */
+fn reload_positional(s: &mut Cursive) {
+    if s.with_user_data(|app: &mut AppData| app.filter.group_completed || app.labels.numbered).unwrap_or(false) {
+        reload_list(s);
+    }
+}
+
+
+/** Used for retrieving a single task by name */
+fn retrieve_task(conn: &Connection, name: &str) -> Option<Task> {
+    return conn.query_row(&format!("{TASK_QUERY} WHERE t.name = ?1"), [name], task_from_row).ok();
+}
+
+
+/** Used for linking a task to its counterpart in an external service, also records the sync time. Returns false if there is no task with the id */
+fn set_remote_id(conn: &Connection, id: i64, remote_id: &str) -> Result<bool> {
+    let updated = conn.execute("UPDATE tasks SET remote_id = ?2, synced_at = datetime('now') WHERE id = ?1", params![id, remote_id])?;
+    return Ok(updated > 0);
+}
+
+
+/** Used for retrieving the task linked to an id of an external service */
+fn get_by_remote_id(conn: &Connection, remote_id: &str) -> Result<Option<Task>> {
+    return conn.query_row(&format!("{TASK_QUERY} WHERE t.remote_id = ?1"), [remote_id], task_from_row).optional();
+}
+
+
+/** Used for re-rendering the rows of the given tasks after their state changed in the database */
+fn refresh_labels(tasks: &mut SelectView<String>, app: &AppData, names: &[String]) {
+    for (row, (label, name)) in tasks.iter_mut().filter(|(_, name)| name.as_str() != GROUP_HEADER).enumerate() {
+        if !names.contains(name) {
+            continue;
+        }
+        if let Some(task) = retrieve_task(&app.conn, name) {
+            let mut styled = task_label(&task, &app.labels);
+            if app.labels.numbered && row < NUMBERED_ROWS {
+                styled = number_label(&styled, row + 1);
+            }
+            *label = truncate_label(&styled, app.label_width);
+        }
+    }
+}
+
+
+/** Used for putting the number key that toggles a row in front of its label */
+fn number_label(label: &StyledString, row: usize) -> StyledString {
+    let mut numbered = StyledString::styled(format!("{row} "), ColorStyle::secondary());
+    numbered.append(label.clone());
+    return numbered;
+}
+
+
+/** Used for toggling the status of the task on a numbered row without selecting it first, rows are counted from 1 */
+fn toggle_row(s: &mut Cursive, row: usize) {
+    let task = s.find_name::<SelectView<String>>("tasks")
+        .and_then(|tasks| tasks.iter().map(|(_, name)| name.clone()).filter(|name| name != GROUP_HEADER).nth(row - 1));
+    if let Some(task) = task {
+        set_status(s, &task);
+    }
+}
+
+
+/** Used for reading how wide the task list is from the list_width setting, None when it takes the full width of the terminal.
+ * Widths that don't fit the terminal are clamped to it
+ */
+fn list_width(conn: &Connection) -> Option<usize> {
+    let value = settings::get(conn, settings::LIST_WIDTH);
+    if value.as_deref().map(str::trim) == Some("full") {
+        return None;
+    }
+    let terminal_width = crossterm::terminal::size().map_or(usize::MAX, |(columns, _)| columns as usize);
+    let width = value.and_then(|value| value.trim().parse().ok()).unwrap_or(LIST_WIDTH);
+    return Some(width.clamp(MIN_LIST_WIDTH, terminal_width.saturating_sub(LIST_CHROME).max(MIN_LIST_WIDTH)));
+}
+
+
+/** Used for computing how wide a task label can be, the list width or the max_label_width setting, whichever is smaller, shrunk to fit narrow terminals */
+fn available_label_width(conn: &Connection) -> usize {
+    let terminal_width = crossterm::terminal::size().map_or(usize::MAX, |(columns, _)| columns as usize);
+    let available = terminal_width.saturating_sub(LIST_CHROME);
+    let mut width = list_width(conn).unwrap_or(available).min(available);
+    let max_width = settings::get_usize(conn, settings::MAX_LABEL_WIDTH, 0);
+    if max_width > 0 {
+        width = width.min(max_width);
+    }
+    return width.max(1);
+}
+
+
+/** Used for cutting a label down to the given width, the end is replaced with an ellipsis and styles are kept */
+fn truncate_label(label: &StyledString, width: usize) -> StyledString {
+    let label_width: usize = label.source().chars().map(|c| c.width().unwrap_or(0)).sum();
+    if label_width <= width {
+        return label.clone();
+    }
+    let mut truncated = StyledString::new();
+    // one column is kept free for the ellipsis
+    let mut remaining = width.saturating_sub(1);
+    for span in label.spans() {
+        let mut content = String::new();
+        for c in span.content.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if char_width > remaining {
+                remaining = 0;
+                break;
+            }
+            remaining -= char_width;
+            content.push(c);
+        }
+        truncated.append_styled(content, *span.attr);
+        if remaining == 0 {
+            break;
+        }
+    }
+    truncated.append_plain("…");
+    return truncated;
+}
+
+
+/** Used for letting tasks be dragged up and down the list with the mouse, a drag that ends on another row moves the task there */
+fn draggable(tasks: NamedView<SelectView<String>>) -> OnEventView<NamedView<SelectView<String>>> {
+    // row the current drag started on
+    let drag_start: Arc<Mutex<Option<usize>>> = Arc::new(Mutex::new(None));
+    return OnEventView::new(tasks).on_pre_event_inner(EventTrigger::mouse(), move |view, event| {
+        let Event::Mouse { offset, position, event } = *event else {
+            return None;
+        };
+        let row = position.checked_sub(offset).map(|position| position.y).filter(|row| *row < view.get_mut().len());
+        let mut drag_start = drag_start.lock().unwrap();
+        match event {
+            MouseEvent::Press(MouseButton::Left) => *drag_start = row,
+            MouseEvent::Release(MouseButton::Left) => {
+                // releasing on the row the drag started on is a normal click, the select view handles it
+                if let (Some(from), Some(to)) = (drag_start.take(), row) && from != to {
+                    return Some(EventResult::Consumed(Some(Callback::from_fn(move |s| move_task(s, from, to)))));
+                }
+            }
+            _ => {}
+        }
+        return None;
+    });
+}
+
+
+/** Used for letting the list jump to the first task starting with what was typed while type-ahead is active.
+ * Typing again after a pause starts over, Backspace removes the last character and Esc leaves type-ahead
+ */
+fn type_ahead(list: OnEventView<NamedView<SelectView<String>>>, state: Arc<Mutex<TypeAhead>>) -> OnEventView<NamedView<SelectView<String>>> {
+    return list.on_pre_event_inner(EventTrigger::any(), move |view, event| {
+        let mut state = state.lock().unwrap();
+        if !state.active {
+            return None;
+        }
+        match *event {
+            Event::Char(c) => {
+                if state.last_key.is_some_and(|last_key| last_key.elapsed() > TYPE_AHEAD_PAUSE) {
+                    state.typed.clear();
+                }
+                state.typed.push(c);
+            }
+            Event::Key(Key::Backspace) => {
+                state.typed.pop();
+            }
+            Event::Key(Key::Esc) => {
+                *state = TypeAhead::default();
+                return Some(EventResult::with_cb(|s| show_type_ahead(s, "")));
+            }
+            // arrow keys, Enter and mouse clicks keep working as usual while typing
+            _ => return None
+        }
+        state.last_key = Some(time::Instant::now());
+        let typed = state.typed.to_lowercase();
+        let mut list = view.get_mut();
+        let found = list.iter().position(|(_, name)| name.to_lowercase().starts_with(&typed));
+        let status = if found.is_some() || typed.is_empty() {
+            format!("Jump to: {}", state.typed)
+        }
+        else {
+            format!("Jump to: {} (no match)", state.typed)
+        };
+        let on_select = found.filter(|_| !typed.is_empty()).map(|index| list.set_selection(index));
+        return Some(EventResult::with_cb(move |s| {
+            if let Some(on_select) = &on_select {
+                on_select(s);
+            }
+            show_type_ahead(s, &status);
+        }));
+    });
+}
+
+
+/** Used for switching type-ahead on, typed characters jump through the list instead of running key bindings until it is left */
+fn start_type_ahead(s: &mut Cursive) {
+    s.with_user_data(|app: &mut AppData| {
+        *app.type_ahead.lock().unwrap() = TypeAhead { active: true, ..TypeAhead::default() };
+    });
+    s.focus_name("tasks").ok();
+    show_type_ahead(s, "Jump to: ");
+}
+
+
+/** Used for showing what was typed to jump below the list, an empty status hides it */
+fn show_type_ahead(s: &mut Cursive, status: &str) {
+    s.call_on_name("type_ahead", |view: &mut TextView| view.set_content(status));
+}
+
+
+/** Used for moving a task to another row and saving the new order */
+fn move_task(s: &mut Cursive, from: usize, to: usize) {
+    if inspect_guard(s) {
+        return;
+    }
+
+    // Nested function for storing the new order of the shown tasks, they swap around the sort positions they already had
+    // so tasks hidden by a filter keep their place
+    fn reorder_tasks(conn: &mut Connection, names: &[String]) -> Result<()> {
+        let tx = conn.transaction()?;
+        let mut positions = names.iter()
+            .map(|name| tx.query_row("SELECT sort_order FROM tasks WHERE name = ?1", [name], |row| row.get(0)))
+            .collect::<Result<Vec<i64>>>()?;
+        positions.sort();
+        for (name, position) in names.iter().zip(positions) {
+            tx.execute("UPDATE tasks SET sort_order = ?2 WHERE name = ?1", params![name, position])?;
+        }
+        return tx.commit();
+    }
+
+    // the shown order isn't the stored one while sorted by effort, saving it would overwrite the manual order
+    if s.with_user_data(|app: &mut AppData| app.filter.effort_sort != EffortSort::Manual).unwrap_or(false) {
+        show_info(s, "Switch back to the manual order to move tasks");
+        return;
+    }
+    let mut tasks = s.find_name::<SelectView<String>>("tasks").unwrap();
+    let Some((label, name)) = tasks.get_item_mut(from).map(|(label, name)| (label.clone(), name.clone())) else {
+        return;
+    };
+    if name == GROUP_HEADER {
+        return;
+    }
+    tasks.remove_item(from);
+    tasks.insert_item(to, label, name);
+    tasks.set_selection(to);
+    let names: Vec<String> = tasks.iter().map(|(_, name)| name.clone()).filter(|name| name != GROUP_HEADER).collect();
+    s.with_user_data(|app: &mut AppData| {
+        reorder_tasks(&mut open_write(&app.db_path), &names).expect("Error saving task order");
+    });
+    // a task dropped on the wrong side of the completed header goes back to its group
+    drop(tasks);
+    reload_positional(s);
+}
+
+
+/** Used for describing the active filter under the list, empty when every task is shown */
+fn filter_status(filter: &Filter) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if filter.someday {
+        parts.push("Someday".to_string());
+    }
+    match filter.recent_days {
+        Some(1) => parts.push("Changed in the last day".to_string()),
+        Some(days) => parts.push(format!("Changed in the last {days} days")),
+        None => {}
+    }
+    match filter.stale_days {
+        Some(1) => parts.push("Open for over a day".to_string()),
+        Some(days) => parts.push(format!("Open for over {days} days")),
+        None => {}
+    }
+    if let Some(source) = &filter.source {
+        parts.push(format!("Source: {source}"));
+    }
+    match filter.effort_sort {
+        EffortSort::EasiestFirst => parts.push("Easiest first".to_string()),
+        EffortSort::HardestFirst => parts.push("Hardest first".to_string()),
+        EffortSort::Manual => {}
+    }
+    return parts.join(", ");
+}
+
+
+/** Used for switching between the list and the someday bucket */
+fn toggle_someday_view(s: &mut Cursive) {
+    s.with_user_data(|app: &mut AppData| app.filter.someday = !app.filter.someday);
+    reload_list(s);
+}
+
+
+/** Used for moving the selected task into the someday bucket, or back out of it when the bucket is shown */
+fn toggle_someday(s: &mut Cursive) {
+    if inspect_guard(s) {
+        return;
+    }
+
+    // Nested function for flipping whether a task is deferred to someday
+    fn update_someday(conn: &Connection, task: &str) {
+        conn.execute("UPDATE tasks SET someday = NOT someday WHERE name = ?1", [task]).expect("Error moving task");
+    }
+
+    // the key can be pressed before the list is shown
+    let Some(mut tasks) = s.find_name::<SelectView<String>>("tasks") else {
+        return;
+    };
+    let Some(index) = tasks.selected_id().filter(|index| tasks.get_item(*index).is_some_and(|(_, name)| name != GROUP_HEADER)) else {
+        show_info(s, "No task to move");
+        return;
+    };
+    let task = tasks.get_item(index).map(|(_, name)| name.clone()).unwrap();
+    s.with_user_data(|app: &mut AppData| update_someday(&open_write(&app.db_path), &task));
+    // the task now belongs to the other view
+    tasks.remove_item(index);
+    drop(tasks);
+    reload_positional(s);
+}
+
+
+/** Used for picking which creation source the list is limited to */
+fn filter_source(s: &mut Cursive) {
+
+    // Nested function for retrieving every source tasks were created by
+    fn get_sources(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn.prepare("SELECT DISTINCT COALESCE(source, 'unknown') FROM tasks ORDER BY 1").expect("Error retrieving task sources");
+        return stmt.query_map([], |row| row.get(0))
+            .expect("Failed to query task sources")
+            .map(|source| source.unwrap())
+            .collect();
+    }
+
+    let sources = s.with_user_data(|app: &mut AppData| get_sources(&app.conn)).unwrap_or_default();
+    let mut picker = SelectView::<Option<String>>::new();
+    picker.add_item("(every source)", None);
+    for source in sources {
+        picker.add_item(source.clone(), Some(source));
+    }
+    open_dialog(s, "source_dialog", Dialog::around(picker
+        .on_submit(|s, source: &Option<String>| {
+            s.pop_layer();
+            s.with_user_data(|app: &mut AppData| app.filter.source = source.clone());
+            reload_list(s);
+        })
+        .scrollable())
+    .title("Filter by source")
+    .button("Cancel", |s| {
+        s.pop_layer();
+    }));
+}
+
+
+/** Used for retrieving everything stored about a task as label/value pairs */
+fn task_details(conn: &Connection, task: &str) -> Result<Vec<(&'static str, String)>> {
+    return conn.query_row(
+        "SELECT t.id, t.completed, t.completed_at, t.updated_at, COALESCE(t.source, 'unknown'), b.name, t.effort, t.created_at,
+            CAST(julianday('now') - julianday(t.created_at) AS INTEGER)
+        FROM tasks t LEFT JOIN tasks b ON b.id = t.blocked_by WHERE t.name = ?1",
+        [task], |row| {
+            let completed: bool = row.get(1)?;
+            Ok(vec![
+                ("Id", row.get::<_, i64>(0)?.to_string()),
+                ("Status", if completed { "completed".to_string() } else { "open".to_string() }),
+                ("Created at", row.get::<_, Option<String>>(7)?.unwrap_or("-".to_string())),
+                ("Age", row.get::<_, Option<i64>>(8)?.map_or("-".to_string(), |days| if days == 1 { "1 day".to_string() } else { format!("{days} days") })),
+                ("Completed at", row.get::<_, Option<String>>(2)?.unwrap_or("-".to_string())),
+                ("Changed at", row.get::<_, Option<String>>(3)?.unwrap_or("-".to_string())),
+                ("Source", row.get(4)?),
+                ("Blocked by", row.get::<_, Option<String>>(5)?.unwrap_or("-".to_string())),
+                ("Effort", effort_dots(row.get(6)?))
+            ])
+        });
+}
+
+
+/** Used for turning the details of a task into one line per detail */
+fn details_text(details: &[(&'static str, String)]) -> String {
+    return details.iter()
+        .map(|(label, value)| format!("{label}: {value}"))
+        .collect::<Vec<String>>()
+        .join("\n");
+}
+
+
+/** Used for showing everything stored about the selected task */
+fn show_details(s: &mut Cursive) {
+    let Some(task) = s.find_name::<SelectView<String>>("tasks").and_then(|tasks| selected_task(&tasks)) else {
+        show_info(s, "No task selected");
+        return;
+    };
+    let details = s.with_user_data(|app: &mut AppData| task_details(&app.conn, &task).expect("Error retrieving task details"))
+        .unwrap_or_default();
+    open_dialog(s, "details_dialog", Dialog::info(details_text(&details)).title(task.as_str()));
+}
+
+
+/** Used for walking through the shown tasks one at a time in their shown order, e.g. for a weekly review */
+fn start_review(s: &mut Cursive) {
+    let names: Vec<String> = s.find_name::<SelectView<String>>("tasks")
+        .map(|tasks| tasks.iter().map(|(_, name)| name.clone()).filter(|name| name != GROUP_HEADER).collect())
+        .unwrap_or_default();
+    if names.is_empty() {
+        show_info(s, "No tasks to review");
+        return;
+    }
+    show_review(s, Arc::new(names), 0);
+}
+
+
+/** Used for showing the review page of the task at index, the review can be stopped at any task */
+fn show_review(s: &mut Cursive, names: Arc<Vec<String>>, index: usize) {
+
+    // Nested function for moving on to the next task, then running an action of the list on the reviewed task.
+    // The next page is shown first so anything the action reports ends up on top of it
+    fn step(s: &mut Cursive, names: &Arc<Vec<String>>, index: usize, action: Option<&str>) {
+        s.pop_layer();
+        show_review(s, names.clone(), index + 1);
+        let Some(action) = action else {
+            return;
+        };
+        if select_task(s, &names[index]) {
+            action_callback(action)(s);
+        }
+    }
+
+    let Some(task) = names.get(index) else {
+        show_info(s, format!("Review finished, {} tasks reviewed", names.len()));
+        return;
+    };
+    let (details, inspect) = s.with_user_data(|app: &mut AppData| (task_details(&app.conn, task).optional(), app.inspect))
+        .expect("Failed to access database");
+    // a task deleted or renamed since the review started is skipped
+    let Some(details) = details.expect("Error retrieving task details") else {
+        show_review(s, names, index + 1);
+        return;
+    };
+    let mut dialog = Dialog::text(format!("{task}\n\n{}", details_text(&details)))
+        .title(format!("Review {} of {}", index + 1, names.len()));
+    let next = names.clone();
+    dialog.add_button("Next", move |s| step(s, &next, index, None));
+    // only looking is allowed while inspecting
+    if !inspect {
+        let completed = details.iter().any(|(label, value)| *label == "Status" && value == "completed");
+        for (button, action) in [(if completed { "Reopen" } else { "Complete" }, "toggle_status"), ("Delete", "delete"), ("Someday", "someday")] {
+            let names = names.clone();
+            dialog.add_button(button, move |s| step(s, &names, index, Some(action)));
+        }
+        // editing opens on top of the review page, once the task is edited the page is shown again with its new name and details
+        let (task, edited) = (task.clone(), names.clone());
+        dialog.add_button("Edit", move |s| {
+            if !select_task(s, &task) {
+                return;
+            }
+            let edited = edited.clone();
+            edit_task(s, Arc::new(move |s, new_name| {
+                let mut renamed = (*edited).clone();
+                renamed[index] = new_name.to_string();
+                s.pop_layer();
+                show_review(s, Arc::new(renamed), index);
+            }));
+        });
+    }
+    dialog.add_button("Stop", |s| {
+        s.pop_layer();
+    });
+    open_dialog(s, "review_dialog", dialog);
+}
+
+
+/** Used for selecting a task in the list by name, false when it isn't shown anymore */
+fn select_task(s: &mut Cursive, task: &str) -> bool {
+    let Some(mut tasks) = s.find_name::<SelectView<String>>("tasks") else {
+        return false;
+    };
+    let index = tasks.iter().position(|(_, name)| name == task);
+    if let Some(index) = index {
+        tasks.set_selection(index);
+        return true;
+    }
+    drop(tasks);
+    show_info(s, format!("'{task}' isn't in the list anymore"));
+    return false;
+}
+
+
+/** Used for rebuilding every row after the filter changed, the selected task stays selected if it is still shown */
+fn reload_list(s: &mut Cursive) {
+    let Some(mut tasks) = s.find_name::<SelectView<String>>("tasks") else {
+        return;
+    };
+    let selected = tasks.selection();
+    let status = s.with_user_data(|app: &mut AppData| {
+        tasks.clear();
+        for (label, name) in retrieve_list(&app.conn, app.limit, &app.labels, &app.filter).0 {
+            tasks.add_item(truncate_label(&label, app.label_width), name);
+        }
+        filter_status(&app.filter)
+    }).unwrap_or_default();
+    let index = selected.and_then(|selected| tasks.iter().position(|(_, name)| *name == *selected));
+    if let Some(index) = index {
+        tasks.set_selection(index);
+    }
+    s.call_on_name("filter_status", |view: &mut TextView| view.set_content(status));
+}
 
 
+/** Used for toggling between every task and only the tasks changed in the last recent_days days */
+fn toggle_recent(s: &mut Cursive) {
+    s.with_user_data(|app: &mut AppData| {
+        app.filter.recent_days = match app.filter.recent_days {
+            Some(_) => None,
+            None => Some(settings::get_usize(&app.conn, settings::RECENT_DAYS, 7))
+        };
+    });
+    reload_list(s);
+}
 
 
+/** Used for cycling the list between the manual order, easiest tasks first and hardest tasks first */
+fn sort_by_effort(s: &mut Cursive) {
+    s.with_user_data(|app: &mut AppData| {
+        app.filter.effort_sort = match app.filter.effort_sort {
+            EffortSort::Manual => EffortSort::EasiestFirst,
+            EffortSort::EasiestFirst => EffortSort::HardestFirst,
+            EffortSort::HardestFirst => EffortSort::Manual
+        };
+    });
+    reload_list(s);
+}
 
-/** Used for storing todo list task data */
-struct Task {
-    name: String,
-    completed: bool
+
+/** Used for saving the list as it is shown, filtered and ordered, to an html page that can be opened in a browser or printed */
+fn export_html(s: &mut Cursive) {
+
+    // Nested function for retrieving what the report shows of a task
+    fn get_row(conn: &Connection, task: &str) -> Result<report::ReportRow> {
+        return conn.query_row(
+            "SELECT t.completed, COALESCE(b.completed = 0, FALSE), t.effort FROM tasks t LEFT JOIN tasks b ON b.id = t.blocked_by WHERE t.name = ?1",
+            [task], |row| Ok(report::ReportRow { name: task.to_string(), completed: row.get(0)?, blocked: row.get(1)?, effort: row.get(2)? }));
+    }
+
+    // Nested function for writing the report, failures are shown instead of closing the list
+    fn ok(s: &mut Cursive, path: &str) {
+        let Some(tasks) = s.find_name::<SelectView<String>>("tasks") else {
+            return;
+        };
+        let names: Vec<String> = tasks.iter().map(|(_, name)| name.clone()).filter(|name| name != GROUP_HEADER).collect();
+        drop(tasks);
+        let (rows, status) = s.with_user_data(|app: &mut AppData| {
+            let rows = names.iter().map(|name| get_row(&app.conn, name)).collect::<Result<Vec<report::ReportRow>>>();
+            (rows, filter_status(&app.filter))
+        }).expect("Failed to access database");
+        let title = if status.is_empty() { "Rusty To-Do List".to_string() } else { format!("Rusty To-Do List ({status})") };
+        let written = rows.map_err(|error| error.to_string())
+            .and_then(|rows| std::fs::write(path, report::html(&title, &rows)).map(|_| rows.len()).map_err(|error| error.to_string()));
+        match written {
+            Ok(count) => {
+                s.pop_layer();
+                show_info(s, format!("Exported {count} tasks to '{path}'"));
+            }
+            Err(error) => show_info(s, format!("Couldn't export to '{path}': {error}"))
+        }
+    }
+
+    open_dialog(s, "export_dialog", Dialog::around(EditView::new()
+        .content("tasks.html")
+        .on_submit(ok)
+        .with_name("export_path")
+        .fixed_width(28))
+    .title("Export to html")
+    .button("Ok", |s| {
+        let path = s.call_on_name("export_path", |view: &mut EditView| view.get_content()).unwrap();
+        ok(s, &path);
+    })
+    .button("Cancel", |s| {
+        s.pop_layer();
+    }));
 }
 
 
+/** Used for showing an effort rating as filled and empty dots, e.g. ●●●○○ for 3 */
+fn effort_dots(effort: Option<i64>) -> String {
+    return match effort {
+        Some(effort) => format!("{}{} ({effort}/5)", "●".repeat(effort as usize), "○".repeat(5 - effort as usize)),
+        None => "-".to_string()
+    };
+}
 
-/** This code is for a CLI to-do list built entirely in Rust with a functioning sqlite database locally on a machine.
- * The CLI was built using the Cursive crate to build views, and rusqlite was used for database operations.
- * Purpose of this project was to become familiar with Rust's crate ecosystem.
- */
-fn main() {
-    // main cursive instance
-    let mut siv = cursive::default();
-    siv.add_global_callback('q', |s| s.quit());
-    // connection and path of database, connection is needed for database creationa & transactions
-    let db_path = "./src/resources/db/tasks.db";
-    let conn = Connection::open(db_path).expect("Failed to open the database");
-    create_table(&conn).expect("Error initializing database");
-    // Retrieving data as vector to add into view
-    let task_list = retrieve_list(&conn);
-    // very important for keeping single instance of database connection to be passed in different functions
-    siv.set_user_data(conn);
 
-    let start = time::Instant::now();
-    let async_view = AsyncProgressView::new(&mut siv, move || {
-        if start.elapsed().as_secs() < 5 {
-            AsyncProgressState::Pending(start.elapsed().as_secs_f32() / 5f32)
-        } 
-        else {
-            // Creating view to populate with clone of fetched data of tasks, plain text is data used for database operations, styled task is how its presented visually
-            let mut tasks_view = SelectView::<String>::new();
-            for styled_task in task_list.clone() {
-                let plain_task = styled_task.source().to_string();
-                tasks_view.add_item(styled_task, plain_task);
-            }
-
-            let tasks = tasks_view
-                .on_submit(set_status)
-                .with_name("tasks")
-                .scrollable()
-                .fixed_size((35, 12));
-
-            let buttons = LinearLayout::horizontal()
-                .child(Button::new("Add", add_todo))
-                .child(Button::new("Delete", remove_todo));
-            AsyncProgressState::Available(Dialog::around(LinearLayout::vertical()
-                .child(tasks)
-                .child(buttons))
-            )
+/** Used for toggling between every task and only the open tasks created more than stale_days days ago */
+fn toggle_stale(s: &mut Cursive) {
+    s.with_user_data(|app: &mut AppData| {
+        app.filter.stale_days = match app.filter.stale_days {
+            Some(_) => None,
+            // the marker can be switched off, the filter still needs a threshold
+            None => Some(settings::get_usize(&app.conn, settings::STALE_DAYS, 30).max(1))
+        };
+    });
+    reload_list(s);
+}
+
+
+/** Used for toggling the id prefix of every row, the choice is saved unless inspecting */
+fn toggle_ids(s: &mut Cursive) {
+    let Some(mut tasks) = s.find_name::<SelectView<String>>("tasks") else {
+        return;
+    };
+    s.with_user_data(|app: &mut AppData| {
+        app.labels.show_ids = !app.labels.show_ids;
+        if !app.inspect {
+            settings::set(&open_write(&app.db_path), settings::SHOW_IDS, &app.labels.show_ids.to_string());
         }
+        let names: Vec<String> = tasks.iter().map(|(_, name)| name.clone()).collect();
+        refresh_labels(&mut tasks, app, &names);
     });
-    siv.add_layer(Dialog::around(async_view).title("Rusty To-Do List"));
-    siv.run();
 }
 
 
-/** Used for creating the database of tasks for the todo list */
-fn create_table(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS tasks (
-            name TEXT PRIMARY KEY,
-            completed BOOLEAN
-    )", [])?;
-    return Ok(());
+/** Used for cutting every label again after the terminal was resized */
+fn refit_labels(s: &mut Cursive) {
+    let Some(mut tasks) = s.find_name::<SelectView<String>>("tasks") else {
+        return;
+    };
+    s.with_user_data(|app: &mut AppData| {
+        app.label_width = available_label_width(&app.conn);
+        let names: Vec<String> = tasks.iter().map(|(_, name)| name.clone()).collect();
+        refresh_labels(&mut tasks, app, &names);
+    });
 }
 
 
-/** Used for retrieving todo list data to be displayed in the cursive view with styling data based on completion*/
-fn retrieve_list(conn: &Connection) -> Vec<StyledString> {
-    let mut result_vec: Vec<StyledString> = Vec::new();
-    let mut stmt = conn.prepare("SELECT name, completed FROM tasks").expect("Error retrieving tasks from database");
+/** Used for retrieving the names of unfinished tasks blocked by the given task */
+fn blocked_tasks(conn: &Connection, blocker: &str) -> Vec<String> {
+    let mut stmt = conn.prepare(
+        "SELECT t.name FROM tasks t JOIN tasks b ON b.id = t.blocked_by WHERE b.name = ?1 AND NOT t.completed")
+        .expect("Error retrieving blocked tasks");
+    return stmt.query_map([blocker], |row| row.get(0))
+        .expect("Failed to query blocked tasks")
+        .map(|name| name.unwrap())
+        .collect();
+}
 
-    let task_iter = stmt.query_map([], |row| {
-        Ok(Task {
-            // task name is tied to column 0, completion state is tied to column 1
-            name: row.get(0)?,
-            completed: row.get(1)?
-        })
-    });
 
-    for task in task_iter.expect("Failed to query tasks") {
-        let unwrapped_task = task.unwrap();
-        if !unwrapped_task.completed {
-            let unfin_task = SpannedString::styled(
-                unwrapped_task.name, 
-                cursive::style::Effect::Simple);
-            result_vec.push(unfin_task);
-        }
-        else {
-            let fin_task = SpannedString::styled(
-                unwrapped_task.name, 
-                cursive::style::Effect::Strikethrough);
-            result_vec.push(fin_task);
-        }
+/** Used for cleaning up a task name before it is stored, line breaks and tabs become spaces and surrounding whitespace is dropped.
+ * None when nothing is left, a task always has a name
+ */
+fn sanitize_name(name: &str) -> Option<String> {
+    let cleaned: String = name.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+    let cleaned = cleaned.trim();
+    if cleaned.is_empty() {
+        return None;
     }
-    return result_vec;
+    return Some(cleaned.to_string());
+}
+
+
+/** Used for checking if a task with the given name already exists */
+fn task_exists(conn: &Connection, name: &str) -> Result<bool> {
+    return conn.query_row("SELECT EXISTS (SELECT 1 FROM tasks WHERE name = ?1)", [name], |row| row.get(0));
+}
+
+
+/** Used for inserting a todo list item into the database, source records how it was created */
+fn insert_data(conn: &Connection, task_name: &str, source: &str) -> Result<()> {
+    conn.execute("INSERT INTO tasks (name, completed, source) VALUES (?1, ?2, ?3)", params![task_name, false, source])?;
+    Ok(())
 }
 
 
 /** Used for adding tasks to the todo list */
 fn add_todo(s: &mut Cursive) {
-
-    // Used for inserting a todo list item into the database
-    fn insert_data(conn: &Connection, task_name: &str) -> Result<()> {
-        conn.execute("INSERT INTO tasks (name, completed) VALUES (?1, ?2)", params![task_name, false])?;
-        Ok(())
+    if inspect_guard(s) {
+        return;
+    }
+    // a key binding can fire before the list is shown, there is nothing to add to yet
+    if s.find_name::<SelectView<String>>("tasks").is_none() {
+        return;
     }
 
-    // Nested function for submission of adding another item
+    // Nested function for submission of adding another item, keeps the dialog open if the name can't be used
     fn ok(s: &mut Cursive, task_name: &str) {
-        s.call_on_name("tasks", |view: &mut SelectView<String>| {
-            view.add_item_str(task_name);
-        });
-        s.with_user_data(|conn: &mut Connection| {
-            insert_data(conn, task_name).expect("Failed to insert item");
-        });
+        let Some(task_name) = sanitize_name(task_name) else {
+            show_info(s, "Task name can't be empty");
+            return;
+        };
+        let task_name = task_name.as_str();
+        if s.with_user_data(|app: &mut AppData| task_exists(&app.conn, task_name).expect("Error checking task name")).unwrap_or(false) {
+            show_info(s, format!("A task named '{task_name}' already exists"));
+            return;
+        }
+        let Some(mut tasks) = s.find_name::<SelectView<String>>("tasks") else {
+            return;
+        };
+        let (add_another, select_new) = s.with_user_data(|app: &mut AppData| {
+            let conn = open_write(&app.db_path);
+            insert_data(&conn, task_name, SOURCE_MANUAL).expect("Failed to insert item");
+            // a task added while the someday bucket is shown belongs in it
+            if app.filter.someday {
+                conn.execute("UPDATE tasks SET someday = TRUE WHERE name = ?1", [task_name]).expect("Error moving task");
+            }
+            tasks.add_item(StyledString::new(), task_name.to_string());
+            refresh_labels(&mut tasks, app, &[task_name.to_string()]);
+            (app.add_another, settings::get_bool(&app.conn, settings::SELECT_NEW_TASK, false))
+        }).unwrap_or_default();
+        // the new task is looked up by name since it isn't necessarily the last row
+        if select_new {
+            let index = tasks.iter().position(|(_, name)| name == task_name);
+            if let Some(index) = index {
+                tasks.set_selection(index);
+            }
+        }
+        drop(tasks);
+        reload_positional(s);
         s.pop_layer();
+        if select_new {
+            s.focus_name("tasks").expect("Failed to focus the task list");
+        }
+        // open a fresh dialog for the next task when adding several in a row
+        if add_another {
+            add_todo(s);
+        }
     }
 
-    s.add_layer(Dialog::around(EditView::new()
-        .on_submit(ok)
-        .with_name("task")
-        .fixed_width(28))
+    let add_another = s.with_user_data(|app: &mut AppData| app.add_another).unwrap_or(false);
+    open_dialog(s, "add_dialog", Dialog::around(LinearLayout::vertical()
+        .child(EditView::new()
+            .on_submit(ok)
+            .with_name("task")
+            .fixed_width(28))
+        .child(LinearLayout::horizontal()
+            .child(Checkbox::new()
+                .with_checked(add_another)
+                .on_change(|s, checked| {
+                    s.with_user_data(|app: &mut AppData| app.add_another = checked);
+                }))
+            .child(TextView::new(" Add another"))))
     .title("Enter task name")
     .button("Ok", |s| {
         let task = s.call_on_name("task", |view: &mut EditView| {
@@ -150,27 +1734,304 @@ fn add_todo(s: &mut Cursive) {
 }
 
 
+/** Used for renaming a task, shared by the edit dialog and the headless edit command */
+fn rename_task(conn: &Connection, old_name: &str, new_name: &str) -> Result<RenameOutcome> {
+    // a missing task is reported as missing even when the new name is taken as well
+    if !task_exists(conn, old_name)? {
+        return Ok(RenameOutcome::NotFound);
+    }
+    if old_name != new_name && task_exists(conn, new_name)? {
+        return Ok(RenameOutcome::NameTaken);
+    }
+    conn.execute("UPDATE tasks SET name = ?2 WHERE name = ?1", [old_name, new_name])?;
+    return Ok(RenameOutcome::Renamed);
+}
+
+
+/** Called with the new name of a task once an edit of it is saved */
+type AfterEdit = Arc<dyn Fn(&mut Cursive, &str) + Send + Sync>;
+
+
+/** Used for editing the name and effort of the selected task */
+fn edit_todo(s: &mut Cursive) {
+    edit_task(s, Arc::new(|_, _| {}));
+}
+
+
+/** Used for editing the name and effort of the selected task, after_edit runs with the new name once the edit is saved */
+fn edit_task(s: &mut Cursive, after_edit: AfterEdit) {
+    if inspect_guard(s) {
+        return;
+    }
+
+    // Nested function for storing the effort of a task, None clears it
+    fn update_effort(conn: &Connection, task: &str, effort: Option<i64>) {
+        conn.execute("UPDATE tasks SET effort = ?2 WHERE name = ?1", params![task, effort])
+            .expect("Error updating task effort");
+    }
+
+    // Nested function for submitting the new name and effort, keeps the dialog open if the name can't be used
+    fn ok(s: &mut Cursive, old_name: &str, new_name: &str, after_edit: &AfterEdit) {
+        let Some(new_name) = sanitize_name(new_name) else {
+            show_info(s, "Task name can't be empty");
+            return;
+        };
+        let new_name = new_name.as_str();
+        let effort = s.find_name::<SelectView<Option<i64>>>("edit_effort")
+            .and_then(|efforts| efforts.selection())
+            .and_then(|effort| *effort);
+        let outcome = s.with_user_data(|app: &mut AppData| {
+            let conn = open_write(&app.db_path);
+            let outcome = rename_task(&conn, old_name, new_name).expect("Failed to rename task");
+            if let RenameOutcome::Renamed = outcome {
+                update_effort(&conn, new_name, effort);
+            }
+            outcome
+        });
+        match outcome {
+            Some(RenameOutcome::NameTaken) => show_info(s, format!("A task named '{new_name}' already exists")),
+            // a headless command can delete the task while it is being edited, the list catches up with it
+            Some(RenameOutcome::NotFound) => {
+                s.pop_layer();
+                reload_list(s);
+                show_info(s, format!("'{old_name}' isn't in the list anymore"));
+            }
+            _ => {
+                s.pop_layer();
+                let mut tasks = s.find_name::<SelectView<String>>("tasks").unwrap();
+                for (_, name) in tasks.iter_mut().filter(|(_, name)| name.as_str() == old_name) {
+                    *name = new_name.to_string();
+                }
+                s.with_user_data(|app: &mut AppData| {
+                    refresh_labels(&mut tasks, app, &[new_name.to_string()]);
+                });
+                drop(tasks);
+                after_edit(s, new_name);
+            }
+        }
+    }
+
+    // a key binding can fire before the list is shown
+    let Some(tasks) = s.find_name::<SelectView<String>>("tasks") else {
+        return;
+    };
+    let Some(old_name) = selected_task(&tasks) else {
+        show_info(s, "No task to edit");
+        return;
+    };
+    drop(tasks);
+    let submit_name = old_name.clone();
+    let submit_after_edit = after_edit.clone();
+    // a task deleted since the list was loaded has no effort, saving the edit reports it as gone
+    let effort: Option<i64> = s.with_user_data(|app: &mut AppData| {
+        app.conn.query_row("SELECT effort FROM tasks WHERE name = ?1", [&old_name], |row| row.get(0)).optional().expect("Error retrieving task effort")
+    }).flatten().flatten();
+    let mut efforts = SelectView::<Option<i64>>::new().popup();
+    efforts.add_item("-", None);
+    for rating in 1..=5 {
+        efforts.add_item(rating.to_string(), Some(rating));
+    }
+    efforts.set_selection(effort.map_or(0, |effort| effort as usize));
+    open_dialog(s, "edit_dialog", Dialog::around(LinearLayout::vertical()
+        .child(EditView::new()
+            .content(old_name.as_str())
+            .on_submit(move |s, new_name| ok(s, &submit_name, new_name, &submit_after_edit))
+            .with_name("edit")
+            .fixed_width(28))
+        .child(LinearLayout::horizontal()
+            .child(TextView::new("Effort (1 easy - 5 hard): "))
+            .child(efforts.with_name("edit_effort"))))
+    .title("Edit task")
+    .button("Ok", move |s| {
+        let new_name = s.call_on_name("edit", |view: &mut EditView| {
+            view.get_content()
+        }).unwrap();
+        ok(s, &old_name, &new_name, &after_edit);
+    })
+    .button("Cancel", |s| {
+        s.pop_layer();
+    }));
+}
+
+
+/** Used for renaming every task whose name contains a text, e.g. to change a project prefix. The renames are previewed before they are applied */
+fn search_replace(s: &mut Cursive) {
+    if inspect_guard(s) {
+        return;
+    }
+
+    // renames as old and new name, and a description of every skipped task
+    #[derive(Default)]
+    struct RenamePlan {
+        renames: Vec<(String, String)>,
+        skipped: Vec<String>
+    }
+
+    // Nested function for working out every rename in list order. Like renaming a single task nothing is merged, a name that would
+    // be taken by an existing task or an earlier rename is skipped along with names that would end up empty
+    fn plan_renames(conn: &Connection, find: &str, replace: &str) -> Result<RenamePlan> {
+        let names = conn.prepare("SELECT name FROM tasks WHERE instr(name, ?1) > 0 ORDER BY sort_order, id")?
+            .query_map([find], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<String>>>()?;
+        let mut renames: Vec<(String, String)> = Vec::new();
+        let mut skipped: Vec<String> = Vec::new();
+        for name in names {
+            let Some(new_name) = sanitize_name(&name.replace(find, replace)) else {
+                skipped.push(format!("{name} (the name would be empty)"));
+                continue;
+            };
+            if new_name == name {
+                continue;
+            }
+            if task_exists(conn, &new_name)? || renames.iter().any(|(_, planned)| *planned == new_name) {
+                skipped.push(format!("{name} ('{new_name}' is taken)"));
+                continue;
+            }
+            renames.push((name, new_name));
+        }
+        return Ok(RenamePlan { renames, skipped });
+    }
+
+    // Nested function for applying every rename in one transaction
+    fn apply_renames(conn: &mut Connection, renames: &[(String, String)]) -> Result<()> {
+        let tx = conn.transaction()?;
+        for (name, new_name) in renames {
+            tx.execute("UPDATE tasks SET name = ?2 WHERE name = ?1", [name, new_name])?;
+        }
+        tx.commit()?;
+        return Ok(());
+    }
+
+    // Nested function for showing what would be renamed, nothing changes until it is applied
+    fn preview(s: &mut Cursive) {
+        let find = s.call_on_name("find_text", |view: &mut EditView| view.get_content()).unwrap();
+        let replace = s.call_on_name("replace_text", |view: &mut EditView| view.get_content()).unwrap();
+        if find.is_empty() {
+            show_info(s, "Enter the text to search for");
+            return;
+        }
+        let RenamePlan { renames, skipped } = s.with_user_data(|app: &mut AppData| plan_renames(&app.conn, &find, &replace).expect("Error searching task names"))
+            .unwrap_or_default();
+        let mut lines: Vec<String> = renames.iter().map(|(name, new_name)| format!("{name} -> {new_name}")).collect();
+        if !skipped.is_empty() {
+            lines.push(String::new());
+            lines.push(format!("Skipped {}:", skipped.len()));
+            lines.extend(skipped);
+        }
+        if renames.is_empty() {
+            lines.insert(0, "No task names would change".to_string());
+            show_info(s, lines.join("\n"));
+            return;
+        }
+        let count = renames.len();
+        let plural = if count == 1 { "task" } else { "tasks" };
+        s.add_layer(Dialog::around(TextView::new(lines.join("\n")).scrollable().max_height(12))
+            .title(format!("Rename {count} {plural}?"))
+            .button("Apply", move |s| {
+                s.pop_layer();
+                s.pop_layer();
+                s.with_user_data(|app: &mut AppData| {
+                    apply_renames(&mut open_write(&app.db_path), &renames).expect("Error renaming tasks");
+                });
+                reload_list(s);
+                show_info(s, format!("Renamed {count} {plural}"));
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }));
+    }
+
+    open_dialog(s, "replace_dialog", Dialog::around(LinearLayout::vertical()
+        .child(TextView::new("Find"))
+        .child(EditView::new().with_name("find_text").fixed_width(28))
+        .child(TextView::new("Replace with"))
+        .child(EditView::new().on_submit(|s, _| preview(s)).with_name("replace_text").fixed_width(28)))
+    .title("Replace in task names")
+    .button("Preview", preview)
+    .button("Cancel", |s| {
+        s.pop_layer();
+    }));
+}
+
+
+/** Used for resetting the selected task back to a bare incomplete task, clearing its completion, blocker, effort and someday */
+fn reset_todo(s: &mut Cursive) {
+    if inspect_guard(s) {
+        return;
+    }
+
+    // Nested function for clearing everything but the name of a task
+    fn reset_data(conn: &Connection, task: &str) {
+        conn.execute("UPDATE tasks SET completed = FALSE, completed_at = NULL, blocked_by = NULL, effort = NULL, someday = FALSE WHERE name = ?1", [task])
+            .expect("Error resetting task");
+    }
+
+    // a key binding can fire before the list is shown
+    let Some(tasks) = s.find_name::<SelectView<String>>("tasks") else {
+        return;
+    };
+    let Some(task) = selected_task(&tasks) else {
+        show_info(s, "No task to reset");
+        return;
+    };
+    drop(tasks);
+    open_dialog(s, "reset_dialog", Dialog::text(format!("Reset '{task}'? Its completion, blocker, effort and someday will be cleared."))
+        .title("Reset task")
+        .button("Reset", move |s| {
+            s.pop_layer();
+            s.with_user_data(|app: &mut AppData| reset_data(&open_write(&app.db_path), &task));
+            // the task can leave the someday bucket or move when sorted by effort, and reopening it blocks its dependents again,
+            // so every row is rebuilt
+            reload_list(s);
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }));
+}
+
+
 /** Used for removing a todo task */
 fn remove_todo(s: &mut Cursive) {
-    
+    if inspect_guard(s) {
+        return;
+    }
+
     // Nested function for deleting task from database
     fn delete_data(conn: &Connection, task_data: &String) {
         conn.execute("DELETE FROM tasks WHERE (name) IS (?1)", [task_data]).expect("Error removing task");
     }
 
-    // get all tasks from the select view
-    let mut tasks = s.find_name::<SelectView<String>>("tasks").unwrap();
+    // get all tasks from the select view, a key binding can fire before the list is shown
+    let Some(mut tasks) = s.find_name::<SelectView<String>>("tasks") else {
+        return;
+    };
     // match the tasks based on the selected id, if the focus matches selected id remove the item
-    match tasks.selected_id(){
-        None => s.add_layer(Dialog::info("No task to remove")),
+    match tasks.selected_id().filter(|focus| tasks.get_item(*focus).is_some_and(|(_, name)| name != GROUP_HEADER)) {
+        None => show_info(s, "No task to remove"),
         Some(focus) => {
             let task_data = tasks.get_item(focus).map(|(_, data)| data.clone()).expect("Failed to access task data for deletion");
+            let after_delete = s.with_user_data(|app: &mut AppData| settings::get(&app.conn, settings::AFTER_DELETE)).flatten();
+            // the row to select is picked among the rows left once the task is gone
+            let target = match after_delete.as_deref().map(str::trim) {
+                Some("previous") => focus.saturating_sub(1),
+                Some("top") => 0,
+                _ => focus.min(tasks.len().saturating_sub(2))
+            };
             tasks.remove_item(focus);
-            s.with_user_data(|conn: &mut Connection| {
-                delete_data(conn, &task_data);
+            if !tasks.is_empty() {
+                tasks.set_selection(target);
+            }
+            s.with_user_data(|app: &mut AppData| {
+                // tasks waiting on the deleted task are no longer blocked by it
+                let dependents = blocked_tasks(&app.conn, &task_data);
+                delete_data(&open_write(&app.db_path), &task_data);
+                refresh_labels(&mut tasks, app, &dependents);
             });
         }
     }
+    // the completed header counts the deleted task
+    drop(tasks);
+    reload_positional(s);
 }
 
 
@@ -184,31 +2045,516 @@ fn set_status(s: &mut Cursive, task: &str) {
     
     // Nested function for updating status
     fn update_status(conn: &Connection, task: &str, status: bool) {
-        conn.execute("UPDATE tasks SET completed = ?2 WHERE name IS ?1", params![task, !status]).expect("Error updating task status");
+        // completion time is kept for archiving, reopening a task clears it
+        conn.execute("UPDATE tasks SET completed = ?2, completed_at = CASE WHEN ?2 THEN datetime('now') END WHERE name IS ?1", params![task, !status])
+            .expect("Error updating task status");
+    }
+
+    if inspect_guard(s) {
+        return;
     }
 
     let mut tasks: cursive::views::ViewRef<SelectView> = s.find_name::<SelectView<String>>("tasks").unwrap();
     if let Some(id) = tasks.selected_id() {
-        let task_data = tasks.get_item(id).map(|(_, data) | data.clone());
-        tasks.remove_item(id);
-        if let Some(data) = task_data {
-            // Using connection that is stored in view to retrieve selected task status, then update it.
-            s.with_user_data(|conn: &mut Connection| {
-                let task_status = get_status(conn, task);
-                update_status(conn, task, task_status);
-                // If the task was false when selected, update it to finished since it was set to true with update and vice versa
-                if !task_status {
-                    let fin_task = SpannedString::styled(task, cursive::style::Effect::Strikethrough);
-                    tasks.insert_item(id, fin_task, data);
-                } 
-                else {
-                    let unfin_task = SpannedString::styled(task, cursive::style::Effect::Simple);
-                    tasks.insert_item(id, unfin_task, data);
+        // Using connection that is stored in view to retrieve selected task status, then update it.
+        let unblocked = s.with_user_data(|app: &mut AppData| {
+            let task_status = get_status(&app.conn, task);
+            update_status(&open_write(&app.db_path), task, task_status);
+            // Re-render the task and every task it blocks, since finishing or reopening it changes whether they are blocked
+            let mut changed = blocked_tasks(&app.conn, task);
+            let dependents = changed.clone();
+            changed.push(task.to_string());
+            refresh_labels(&mut tasks, app, &changed);
+            // If the task was false when selected it is now finished, so its dependents are unblocked
+            if !task_status { dependents } else { Vec::new() }
+        }).unwrap_or_default();
+        tasks.set_selection(id);
+        drop(tasks);
+        reload_positional(s);
+        if !unblocked.is_empty() {
+            show_info(s, format!("Now unblocked:\n{}", unblocked.join("\n")));
+        }
+    }
+}
+
+
+/** Used for marking the selected task as blocked by another task, or clearing its blocker */
+fn set_blocker(s: &mut Cursive) {
+    if inspect_guard(s) {
+        return;
+    }
+
+    // Nested function for checking if blocking task by blocker would make the two tasks wait on each other
+    fn creates_cycle(conn: &Connection, task: &str, blocker: &str) -> bool {
+        return conn.query_row(
+            "WITH RECURSIVE chain(id) AS (
+                SELECT id FROM tasks WHERE name = ?2
+                UNION SELECT t.blocked_by FROM tasks t JOIN chain c ON t.id = c.id WHERE t.blocked_by IS NOT NULL
+            )
+            SELECT EXISTS (SELECT 1 FROM chain JOIN tasks t ON t.id = chain.id WHERE t.name = ?1)",
+            [task, blocker], |row| row.get(0)).expect("Error checking task dependencies");
+    }
+
+    // Nested function for storing the blocker of a task, an empty blocker clears it
+    fn update_blocker(conn: &Connection, task: &str, blocker: &str) {
+        conn.execute("UPDATE tasks SET blocked_by = (SELECT id FROM tasks WHERE name = ?2) WHERE name = ?1", [task, blocker])
+            .expect("Error updating task blocker");
+    }
+
+    // a key binding can fire before the list is shown
+    let Some(tasks) = s.find_name::<SelectView<String>>("tasks") else {
+        return;
+    };
+    let Some(task) = selected_task(&tasks) else {
+        show_info(s, "No task to block");
+        return;
+    };
+    // every other task can be picked as the blocker
+    let mut blockers = SelectView::<String>::new();
+    blockers.add_item("(not blocked)", String::new());
+    for (_, name) in tasks.iter().filter(|(_, name)| **name != task && name.as_str() != GROUP_HEADER) {
+        blockers.add_item_str(name);
+    }
+    drop(tasks);
+
+    open_dialog(s, "block_dialog", Dialog::around(blockers
+        .on_submit(move |s, blocker: &String| {
+            let cycle = s.with_user_data(|app: &mut AppData| {
+                if !blocker.is_empty() && creates_cycle(&app.conn, &task, blocker) {
+                    return true;
                 }
+                update_blocker(&open_write(&app.db_path), &task, blocker);
+                return false;
+            }).unwrap_or(false);
+            if cycle {
+                show_info(s, format!("{blocker} already waits on {task}"));
+                return;
+            }
+            s.pop_layer();
+            let mut tasks = s.find_name::<SelectView<String>>("tasks").unwrap();
+            s.with_user_data(|app: &mut AppData| {
+                refresh_labels(&mut tasks, app, std::slice::from_ref(&task));
             });
+        })
+        .scrollable()
+        .fixed_size((LIST_WIDTH, 12)))
+    .title("Blocked by")
+    .button("Cancel", |s| {
+        s.pop_layer();
+    }));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /** Used for opening an empty task database in memory with the current schema */
+    fn memory_database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        create_table(&conn).unwrap();
+        migrate(&mut conn).unwrap();
+        settings::create_table(&conn).unwrap();
+        return conn;
+    }
+
+
+    /** Used for picking a fresh database file for tests that need more than one connection, which an in-memory database can't share */
+    fn temp_database(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("todo_list_{name}_{}.db", std::process::id()));
+        std::fs::remove_file(&path).ok();
+        return path.to_string_lossy().into_owned();
+    }
+
+
+    /** Used for building the app the way main does on a fresh database, without a terminal and without the progress bar before the list.
+     * Settings are stored before the app is built, so they shape its key bindings, labels and filters like they would at startup
+     */
+    fn test_app(name: &str, settings: &[(&str, &str)]) -> (Cursive, String) {
+        let path = temp_database(name);
+        let conn = open_database(&path).unwrap();
+        for (key, value) in settings {
+            settings::set(&conn, key, value);
         }
-        tasks.set_selection(id);
+        let mut siv = Cursive::new();
+        bind_keys(&mut siv, &conn);
+        drop(conn);
+        siv.set_user_data(app_data(open_read_only(&path), path.clone(), false, None));
+        let data = siv.with_user_data(|app: &mut AppData| list_data(app, None)).unwrap();
+        siv.add_layer(Dialog::around(list_view(data, None)).title("Rusty To-Do List"));
+        return (siv, path);
+    }
+
+
+    /** Used for typing a text into the focused view one key at a time */
+    fn type_text(siv: &mut Cursive, text: &str) {
+        for c in text.chars() {
+            siv.on_event(Event::Char(c));
+        }
+    }
+
+
+    /** Used for retrieving the name and label of every row of the list */
+    fn shown_rows(siv: &mut Cursive) -> Vec<(String, StyledString)> {
+        let mut tasks = siv.find_name::<SelectView<String>>("tasks").unwrap();
+        return tasks.iter_mut().map(|(label, name)| (name.clone(), label.clone())).collect();
+    }
+
+
+    /** Used for pressing a button of an open dialog */
+    fn press_button(siv: &mut Cursive, dialog: &str, button: &str) {
+        use cursive::View;
+
+        let result = siv.find_name::<Dialog>(dialog).unwrap()
+            .buttons_mut()
+            .find(|found| found.label() == format!("<{button}>"))
+            .unwrap()
+            .on_event(Event::Key(Key::Enter));
+        result.process(siv);
+    }
+
+
+    /** Used for retrieving every task name in list order */
+    fn task_names(conn: &Connection) -> Vec<String> {
+        return conn.prepare("SELECT name FROM tasks ORDER BY sort_order, id").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .map(|name| name.unwrap())
+            .collect();
+    }
+
+
+    #[test]
+    fn rename_task_outcomes() {
+        let conn = memory_database();
+        insert_data(&conn, "Walk dog", SOURCE_MANUAL).unwrap();
+        insert_data(&conn, "Feed cat", SOURCE_MANUAL).unwrap();
+
+        assert!(matches!(rename_task(&conn, "Feed cat", "Feed the cat").unwrap(), RenameOutcome::Renamed));
+        assert!(matches!(rename_task(&conn, "Nope", "Anything").unwrap(), RenameOutcome::NotFound));
+        // a missing task isn't reported as a collision even when the new name is taken
+        assert!(matches!(rename_task(&conn, "Nope", "Walk dog").unwrap(), RenameOutcome::NotFound));
+        assert!(matches!(rename_task(&conn, "Feed the cat", "Walk dog").unwrap(), RenameOutcome::NameTaken));
+        // keeping the name doesn't collide with the task itself
+        assert!(matches!(rename_task(&conn, "Walk dog", "Walk dog").unwrap(), RenameOutcome::Renamed));
+        assert_eq!(task_names(&conn), ["Walk dog", "Feed the cat"]);
+    }
+
+
+    #[test]
+    fn write_while_list_reads() {
+        let path = temp_database("concurrent");
+        let conn = open_database(&path).unwrap();
+        insert_data(&conn, "Walk dog", SOURCE_MANUAL).unwrap();
+        insert_data(&conn, "Feed cat", SOURCE_MANUAL).unwrap();
+        drop(conn);
+        let reader = open_read_only(&path);
+        // the list is in the middle of reading, which holds the database's shared lock
+        let mut stmt = reader.prepare("SELECT name FROM tasks ORDER BY id").unwrap();
+        let mut rows = stmt.query([]).unwrap();
+        assert_eq!(rows.next().unwrap().unwrap().get::<_, String>(0).unwrap(), "Walk dog");
+        // a mutation of the list and a headless command write from their own connections at the same time
+        let writes = [
+            std::thread::spawn({
+                let path = path.clone();
+                move || insert_data(&open_write(&path), "Water plants", SOURCE_MANUAL).is_ok()
+            }),
+            std::thread::spawn({
+                let path = path.clone();
+                move || open_database(&path).map_err(cli::CliError::from)
+                    .and_then(|conn| cli::run(&conn, &["capture".to_string(), "Call mom".to_string()])).is_ok()
+            })
+        ];
+        let started = time::Instant::now();
+        std::thread::sleep(time::Duration::from_millis(300));
+        assert!(writes.iter().all(|write| !write.is_finished()), "the writes wait for the read instead of committing under it");
+        // the read finishes, the writes go through well within the busy timeout
+        assert_eq!(rows.next().unwrap().unwrap().get::<_, String>(0).unwrap(), "Feed cat");
+        drop(rows);
+        drop(stmt);
+        for write in writes {
+            assert!(write.join().unwrap(), "a write gave up waiting for the read");
+        }
+        assert!(started.elapsed() < BUSY_TIMEOUT);
+        let names = task_names(&reader);
+        assert!(names.contains(&"Water plants".to_string()) && names.contains(&"Call mom".to_string()));
+        // the reader can't write, so it never takes the write lock
+        assert!(insert_data(&reader, "Buy milk", SOURCE_MANUAL).is_err());
+        drop(reader);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    #[test]
+    fn archiving_keeps_task_metadata() {
+        let mut conn = memory_database();
+        insert_data(&conn, "Walk dog", SOURCE_CAPTURE).unwrap();
+        insert_data(&conn, "Feed cat", SOURCE_MANUAL).unwrap();
+        conn.execute("UPDATE tasks SET completed = TRUE, completed_at = datetime('now', '-10 days'), effort = 3, created_at = '2024-01-01 00:00:00'
+            WHERE name = 'Walk dog'", []).unwrap();
+        set_remote_id(&conn, 1, "remote-1").unwrap();
+        assert_eq!(auto_archive(&mut conn, 7).unwrap(), 1);
+        assert_eq!(task_names(&conn), ["Feed cat"]);
+        let archived: (String, Option<String>, bool, String, i64, String) = conn.query_row(
+            "SELECT name, remote_id, synced_at IS NOT NULL, source, effort, created_at FROM archived_tasks", [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?))).unwrap();
+        assert_eq!(archived, ("Walk dog".to_string(), Some("remote-1".to_string()), true, SOURCE_CAPTURE.to_string(), 3, "2024-01-01 00:00:00".to_string()));
+    }
+
+
+    #[test]
+    fn effort_and_someday_count_as_changes() {
+        let conn = memory_database();
+        insert_data(&conn, "Walk dog", SOURCE_MANUAL).unwrap();
+        for change in ["effort = 2", "someday = TRUE"] {
+            conn.execute("UPDATE tasks SET updated_at = '2000-01-01 00:00:00'", []).unwrap();
+            conn.execute(&format!("UPDATE tasks SET {change}"), []).unwrap();
+            let changed: bool = conn.query_row("SELECT updated_at > '2000-01-01 00:00:00' FROM tasks", [], |row| row.get(0)).unwrap();
+            assert!(changed, "{change} didn't update updated_at");
+        }
+    }
+
+
+    #[test]
+    fn group_header_never_names_a_task() {
+        let conn = memory_database();
+        insert_data(&conn, "Walk dog", SOURCE_MANUAL).unwrap();
+        // older versions accepted empty names
+        conn.execute("INSERT INTO tasks (name, completed) VALUES ('', TRUE)", []).unwrap();
+        let labels = load_labels(&conn);
+        let filter = Filter { group_completed: true, ..Filter::default() };
+        let names: Vec<String> = retrieve_list(&conn, None, &labels, &filter).0.into_iter().map(|(_, name)| name).collect();
+        assert_eq!(names, ["Walk dog", GROUP_HEADER, ""]);
+        assert_eq!(sanitize_name(&format!("a{GROUP_HEADER}b")).as_deref(), Some("a b"));
+        let mut tasks = SelectView::<String>::new();
+        tasks.add_all_str(names);
+        tasks.set_selection(2);
+        assert_eq!(selected_task(&tasks).as_deref(), Some(""));
+        tasks.set_selection(1);
+        assert_eq!(selected_task(&tasks), None);
+    }
+
+
+    #[test]
+    fn stale_filter_matches_marker() {
+        let conn = memory_database();
+        for (name, days) in [("Fresh", 29.5), ("Almost stale", 30.5), ("Stale", 31.5)] {
+            conn.execute("INSERT INTO tasks (name, completed, created_at) VALUES (?1, FALSE, datetime('now', ?2))",
+                params![name, format!("-{days} days")]).unwrap();
+        }
+        let labels = load_labels(&conn);
+        let marked: Vec<String> = retrieve_list(&conn, None, &labels, &Filter::default()).0.into_iter()
+            .filter(|(label, _)| label.source().starts_with("🕸"))
+            .map(|(_, name)| name)
+            .collect();
+        let filter = Filter { stale_days: Some(30), ..Filter::default() };
+        let filtered: Vec<String> = retrieve_list(&conn, None, &labels, &filter).0.into_iter().map(|(_, name)| name).collect();
+        assert_eq!(marked, ["Stale"]);
+        assert_eq!(filtered, marked);
+    }
+
+
+    #[test]
+    fn id_migration_keeps_bad_rows() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        // a hand edited table from before tasks had ids
+        conn.execute_batch("CREATE TABLE tasks (name TEXT, completed BOOLEAN);
+            INSERT INTO tasks VALUES ('Walk dog', FALSE), (NULL, TRUE), ('Feed cat', TRUE), ('Walk dog', TRUE);").unwrap();
+        create_table(&conn).unwrap();
+        migrate(&mut conn).unwrap();
+        assert_eq!(task_names(&conn), ["Walk dog", "Feed cat"]);
+        let walk_dog: bool = conn.query_row("SELECT completed FROM tasks WHERE name = 'Walk dog'", [], |row| row.get(0)).unwrap();
+        assert!(!walk_dog, "the first row of a duplicate name is kept");
+        let quarantined = conn.prepare("SELECT name, completed, reason FROM quarantined_tasks ORDER BY rowid").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+            .collect::<Result<Vec<(Option<String>, bool, String)>>>().unwrap();
+        assert_eq!(quarantined, [(None, true, "missing name".to_string()), (Some("Walk dog".to_string()), true, "duplicate name".to_string())]);
+    }
+
+
+    #[test]
+    fn unreadable_rows_are_skipped() {
+        let conn = memory_database();
+        insert_data(&conn, "Walk dog", SOURCE_MANUAL).unwrap();
+        insert_data(&conn, "Feed cat", SOURCE_MANUAL).unwrap();
+        // a status edited by hand into something that isn't a boolean
+        conn.execute("UPDATE tasks SET completed = 'maybe' WHERE name = 'Feed cat'", []).unwrap();
+        insert_data(&conn, "Water plants", SOURCE_MANUAL).unwrap();
+        let labels = load_labels(&conn);
+        let (rows, skipped) = retrieve_list(&conn, None, &labels, &Filter::default());
+        let names: Vec<String> = rows.into_iter().map(|(_, name)| name).collect();
+        assert_eq!(names, ["Walk dog", "Water plants"]);
+        assert_eq!(skipped, 1);
     }
-}
 
 
+    #[test]
+    fn add_toggle_delete_through_the_ui() {
+        let (mut siv, path) = test_app("ui_flow", &[("key.add", "a"), ("key.delete", "Del")]);
+        let conn = open_read_only(&path);
+        assert!(shown_rows(&mut siv).is_empty());
+
+        // add opens a dialog, the name is typed into it and submitted with Enter
+        siv.on_event(Event::Char('a'));
+        assert!(siv.find_name::<Dialog>("add_dialog").is_some());
+        type_text(&mut siv, "Walk dog");
+        siv.on_event(Event::Key(Key::Enter));
+        assert!(siv.find_name::<Dialog>("add_dialog").is_none(), "the add dialog is closed once the task is added");
+        siv.on_event(Event::Char('a'));
+        type_text(&mut siv, "Feed cat");
+        siv.on_event(Event::Key(Key::Enter));
+        assert_eq!(task_names(&conn), ["Walk dog", "Feed cat"]);
+        let rows = shown_rows(&mut siv);
+        assert_eq!(rows.iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>(), ["Walk dog", "Feed cat"]);
+
+        // Enter on the selected row toggles it, in the database and in how the row looks
+        siv.on_event(Event::Key(Key::Down));
+        siv.on_event(Event::Key(Key::Enter));
+        let completed: bool = conn.query_row("SELECT completed FROM tasks WHERE name = 'Feed cat'", [], |row| row.get(0)).unwrap();
+        assert!(completed);
+        let (_, label) = &shown_rows(&mut siv)[1];
+        assert!(label.spans().all(|span| *span.attr == CompletedStyle::default().style));
+        siv.on_event(Event::Char('x'));
+        let completed: bool = conn.query_row("SELECT completed FROM tasks WHERE name = 'Feed cat'", [], |row| row.get(0)).unwrap();
+        assert!(!completed, "the toggle_status key toggles it back");
+
+        // deleting removes the selected task from both and selects the task left
+        siv.on_event(Event::Key(Key::Del));
+        assert_eq!(task_names(&conn), ["Walk dog"]);
+        assert_eq!(shown_rows(&mut siv).iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>(), ["Walk dog"]);
+        assert_eq!(siv.find_name::<SelectView<String>>("tasks").and_then(|tasks| selected_task(&tasks)).as_deref(), Some("Walk dog"));
+
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    #[test]
+    fn settings_shape_the_list() {
+        let (mut siv, path) = test_app("settings", &[("key.add", "a"), ("number_keys", "true"), ("group_completed", "true"), ("show_ids", "true")]);
+        for name in ["Walk dog", "Feed cat"] {
+            siv.on_event(Event::Char('a'));
+            type_text(&mut siv, name);
+            siv.on_event(Event::Key(Key::Enter));
+        }
+        // the number key toggles the first row, which moves it under the completed header
+        siv.on_event(Event::Char('1'));
+        let rows: Vec<(String, String)> = shown_rows(&mut siv).into_iter().map(|(name, label)| (name, label.source().to_string())).collect();
+        assert_eq!(rows, [
+            ("Feed cat".to_string(), "1 #2 Feed cat".to_string()),
+            (GROUP_HEADER.to_string(), "▾ Completed (1)".to_string()),
+            ("Walk dog".to_string(), "2 #1 Walk dog".to_string())
+        ]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    #[test]
+    fn bindings_before_the_list_is_shown() {
+        let bindings = [("key.add", "a"), ("key.edit", "E"), ("key.delete", "Del"), ("key.block", "B"), ("key.reset", "X")];
+        let (mut siv, path) = test_app("no_list", &bindings);
+        // the list isn't there yet while the progress bar or the large list dialog is shown
+        while siv.pop_layer().is_some() {}
+        let conn = open_read_only(&path);
+        for (action, event) in keybindings::load(&conn).0 {
+            siv.on_event(event);
+            // the buttons of the list do nothing without it
+            if ["add", "edit", "block", "reset"].contains(&action) {
+                assert!(siv.find_name::<Dialog>(&format!("{action}_dialog")).is_none(), "{action} opened a dialog without a list");
+            }
+            // whatever an action opens is closed so the next key reaches the global bindings again
+            while siv.pop_layer().is_some() {}
+        }
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    #[test]
+    fn repeated_keys_show_a_message_once() {
+        let (mut siv, path) = test_app("repeated_keys", &[]);
+        let layers = siv.screen().len();
+        // on an empty list each key only has a message to show, pressing it again while the message is open adds nothing
+        for key in ['W', 'W', 'W', 'd', 'd', 'd'] {
+            siv.on_event(Event::Char(key));
+        }
+        assert_eq!(siv.screen().len(), layers + 2);
+        assert!(siv.find_name::<Dialog>("info: No tasks to review").is_some());
+        assert!(siv.find_name::<Dialog>("info: No task selected").is_some());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    #[test]
+    fn edit_a_task_deleted_meanwhile() {
+        let (mut siv, path) = test_app("edit_deleted", &[("key.add", "a"), ("key.edit", "E")]);
+        for name in ["Walk dog", "Feed cat"] {
+            siv.on_event(Event::Char('a'));
+            type_text(&mut siv, name);
+            siv.on_event(Event::Key(Key::Enter));
+        }
+        // deleted by a headless command after the list was loaded, the dialog still opens and saving reports it instead of renaming
+        open_write(&path).execute("DELETE FROM tasks WHERE name = 'Walk dog'", []).unwrap();
+        siv.on_event(Event::Char('E'));
+        siv.call_on_name("edit", |view: &mut EditView| view.set_content("Walk the dog"));
+        press_button(&mut siv, "edit_dialog", "Ok");
+        assert!(siv.find_name::<Dialog>("edit_dialog").is_none());
+        assert_eq!(shown_rows(&mut siv).iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>(), ["Feed cat"]);
+        assert_eq!(task_names(&open_read_only(&path)), ["Feed cat"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    #[test]
+    fn review_goes_on_with_an_edited_task() {
+        let (mut siv, path) = test_app("review_edit", &[("key.add", "a")]);
+        for name in ["Walk dog", "Feed cat"] {
+            siv.on_event(Event::Char('a'));
+            type_text(&mut siv, name);
+            siv.on_event(Event::Key(Key::Enter));
+        }
+        siv.on_event(Event::Char('W'));
+        press_button(&mut siv, "review_dialog", "Edit");
+        siv.call_on_name("edit", |view: &mut EditView| view.set_content("Walk the dog"));
+        press_button(&mut siv, "edit_dialog", "Ok");
+        // the page is shown again for the renamed task, so completing it works
+        assert!(siv.find_name::<Dialog>("edit_dialog").is_none());
+        assert_eq!(siv.find_name::<Dialog>("review_dialog").unwrap().get_title(), "Review 1 of 2");
+        press_button(&mut siv, "review_dialog", "Complete");
+        let conn = open_read_only(&path);
+        let completed: bool = conn.query_row("SELECT completed FROM tasks WHERE name = 'Walk the dog'", [], |row| row.get(0)).unwrap();
+        assert!(completed);
+        assert_eq!(siv.find_name::<Dialog>("review_dialog").unwrap().get_title(), "Review 2 of 2");
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+
+    #[test]
+    fn truncate_label_widths() {
+        // Nested function for the plain text of a label
+        fn text(label: &StyledString) -> String {
+            return label.spans().map(|span| span.content).collect();
+        }
+
+        let plain = StyledString::plain("Walk dog");
+        assert_eq!(text(&truncate_label(&plain, 1)), "…");
+        assert_eq!(text(&truncate_label(&plain, 8)), "Walk dog");
+        assert_eq!(text(&truncate_label(&plain, 7)), "Walk d…");
+        // a wide character that doesn't fit the columns left is dropped whole instead of being split
+        let wide = StyledString::plain("ab🎉cd");
+        assert_eq!(text(&truncate_label(&wide, 4)), "ab…");
+        assert_eq!(text(&truncate_label(&wide, 5)), "ab🎉…");
+        // the stale cobweb takes a single column
+        assert_eq!(text(&truncate_label(&StyledString::plain("🕸 Renew passport"), 4)), "🕸 R…");
+        // every span keeps its own style up to the cut
+        let mut spans = StyledString::styled("#12 ", ColorStyle::secondary());
+        spans.append_styled("Walk dog", Effect::Strikethrough);
+        let truncated = truncate_label(&spans, 8);
+        assert_eq!(text(&truncated), "#12 Wal…");
+        let styles: Vec<_> = truncated.spans().map(|span| *span.attr).collect();
+        assert_eq!(styles, [Style::from(ColorStyle::secondary()), Style::from(Effect::Strikethrough), Style::none()]);
+        // a cut right at the end of a span leaves the following spans out
+        assert_eq!(text(&truncate_label(&spans, 5)), "#12 …");
+    }
+}
@@ -0,0 +1,69 @@
+/** Page the report is rendered into, {title}, {summary} and {rows} are replaced. Kept in the binary so an export never depends on
+ * files next to it, and styled inline so the page can be opened or printed to PDF on its own
+ */
+const TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+    body { font-family: sans-serif; max-width: 40em; margin: 2em auto; color: #222; }
+    h1 { font-size: 1.4em; }
+    ul { list-style: none; padding: 0; }
+    li { padding: 0.3em 0; border-bottom: 1px solid #ddd; }
+    input { margin-right: 0.5em; }
+    .completed .name { text-decoration: line-through; color: #888; }
+    .blocked { font-size: 0.8em; color: #b35900; margin-right: 0.5em; }
+    .effort { float: right; color: #666; letter-spacing: 0.1em; }
+    .summary { color: #666; }
+    @media print { li { break-inside: avoid; } }
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p class="summary">{summary}</p>
+<ul>
+{rows}</ul>
+</body>
+</html>
+"#;
+
+
+/** Used for storing one task of the report */
+pub struct ReportRow {
+    pub name: String,
+    pub completed: bool,
+    pub blocked: bool,
+    pub effort: Option<i64>
+}
+
+
+/** Used for rendering the tasks into a self-contained html page, in the order they are given */
+pub fn html(title: &str, rows: &[ReportRow]) -> String {
+    let completed = rows.iter().filter(|row| row.completed).count();
+    let mut items = String::new();
+    for row in rows {
+        let class = if row.completed { " class=\"completed\"" } else { "" };
+        let checked = if row.completed { " checked" } else { "" };
+        let blocked = if row.blocked { "<span class=\"blocked\">blocked</span>" } else { "" };
+        let effort = row.effort
+            .map(|effort| format!("<span class=\"effort\" title=\"effort {effort} of 5\">{}</span>", "●".repeat(effort as usize)))
+            .unwrap_or_default();
+        items.push_str(&format!("<li{class}><input type=\"checkbox\" disabled{checked}>{blocked}<span class=\"name\">{}</span>{effort}</li>\n",
+            escape(&row.name)));
+    }
+    return TEMPLATE
+        .replace("{title}", &escape(title))
+        .replace("{summary}", &format!("{completed} of {} tasks completed", rows.len()))
+        .replace("{rows}", &items);
+}
+
+
+/** Used for escaping text so task names can't break the page */
+fn escape(text: &str) -> String {
+    return text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+}
@@ -0,0 +1,164 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+use crate::parse_completed_style;
+
+
+/** Task count above which a warning is shown at startup offering a limited view, 0 disables the warning */
+pub const LARGE_LIST_THRESHOLD: &str = "large_list_threshold";
+
+/** Tasks completed more than this many days ago are moved to the archive at startup, 0 disables archiving */
+pub const AUTO_ARCHIVE_DAYS: &str = "auto_archive_days";
+
+/** Labels wider than this many columns are cut off with an ellipsis, 0 only cuts labels that don't fit the list */
+pub const MAX_LABEL_WIDTH: &str = "max_label_width";
+
+/** Whether every row is prefixed with the task id, toggled with 'I' */
+pub const SHOW_IDS: &str = "show_ids";
+
+/** Whether a newly added task is selected right away */
+pub const SELECT_NEW_TASK: &str = "select_new_task";
+
+/** How completed tasks are marked, e.g. `prefix:[x], strikethrough, color:green` */
+pub const COMPLETED_STYLE: &str = "completed_style";
+
+/** What Enter does on a task, `toggle` its status or show its `details` */
+pub const ENTER_ACTION: &str = "enter_action";
+
+/** Which task is selected after deleting one, the `next` one, the `previous` one or the `top` of the list */
+pub const AFTER_DELETE: &str = "after_delete";
+
+/** Whether completed tasks are shown under one collapsible header at the bottom of the list */
+pub const GROUP_COMPLETED: &str = "group_completed";
+
+/** Whether the completed group is folded away, toggled by activating its header */
+pub const COMPLETED_COLLAPSED: &str = "completed_collapsed";
+
+/** Open tasks created more than this many days ago are marked as stale, 0 disables the marker */
+pub const STALE_DAYS: &str = "stale_days";
+
+/** How many columns wide the task list is, `full` makes it as wide as the terminal */
+pub const LIST_WIDTH: &str = "list_width";
+
+/** Whether the first nine rows are numbered and the number keys toggle their status */
+pub const NUMBER_KEYS: &str = "number_keys";
+
+/** How many days back the recently changed filter reaches */
+pub const RECENT_DAYS: &str = "recent_days";
+
+
+/** Used for creating the key/value table of settings, settings are stored in the same database as the tasks */
+pub fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT
+    )", [])?;
+    return Ok(());
+}
+
+
+/** Used for retrieving the raw value of a setting, None if it was never set or can't be read */
+pub fn get(conn: &Connection, key: &str) -> Option<String> {
+    // values written by hand without quotes are stored as numbers, casting reads them back as text
+    return conn.query_row("SELECT CAST(value AS TEXT) FROM settings WHERE key = ?1", [key], |row| row.get(0))
+        .optional()
+        .unwrap_or_else(|error| {
+            log::warn!("Ignoring setting {key}: {error}");
+            None
+        });
+}
+
+
+/** Used for storing a setting, replacing any previous value */
+pub fn set(conn: &Connection, key: &str, value: &str) {
+    conn.execute("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)", params![key, value])
+        .expect("Error saving setting");
+}
+
+
+/** Used for retrieving a numeric setting, falls back to the default when unset or not a number */
+pub fn get_usize(conn: &Connection, key: &str, default: usize) -> usize {
+    return get(conn, key).and_then(|value| value.trim().parse().ok()).unwrap_or(default);
+}
+
+
+/** Used for retrieving an on/off setting, falls back to the default when unset or not true/false */
+pub fn get_bool(conn: &Connection, key: &str, default: bool) -> bool {
+    return get(conn, key).and_then(|value| value.trim().parse().ok()).unwrap_or(default);
+}
+
+
+/** Used for finding settings whose values can't be used, they are ignored in favour of their defaults instead of stopping startup.
+ * Keys that aren't known settings are left alone
+ */
+pub fn check(conn: &Connection) -> Vec<String> {
+    let mut problems: Vec<String> = Vec::new();
+    for key in [LARGE_LIST_THRESHOLD, AUTO_ARCHIVE_DAYS, MAX_LABEL_WIDTH, RECENT_DAYS, STALE_DAYS] {
+        if let Some(value) = get(conn, key) && value.trim().parse::<usize>().is_err() {
+            problems.push(format!("{key} = '{value}' is not a whole number"));
+        }
+    }
+    for key in [SHOW_IDS, SELECT_NEW_TASK, GROUP_COMPLETED, COMPLETED_COLLAPSED, NUMBER_KEYS] {
+        if let Some(value) = get(conn, key) && value.trim().parse::<bool>().is_err() {
+            problems.push(format!("{key} = '{value}' is not true or false"));
+        }
+    }
+    if let Some(value) = get(conn, LIST_WIDTH) && value.trim() != "full" && value.trim().parse::<usize>().is_err() {
+        problems.push(format!("{LIST_WIDTH} = '{value}' is not a whole number or full"));
+    }
+    let choices: [(&str, &[&str]); 2] = [(ENTER_ACTION, &["toggle", "details"]), (AFTER_DELETE, &["next", "previous", "top"])];
+    for (key, allowed) in choices {
+        if let Some(value) = get(conn, key) && !allowed.contains(&value.trim()) {
+            problems.push(format!("{key} = '{value}' is not one of {}", allowed.join(", ")));
+        }
+    }
+    if let Some(value) = get(conn, COMPLETED_STYLE) && let Err(problem) = parse_completed_style(&value) {
+        problems.push(format!("{COMPLETED_STYLE} = '{value}' can't be used, {problem}"));
+    }
+    return problems;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    /** Used for opening an empty settings table in memory holding the given values, written as sql so they can be stored unquoted */
+    fn settings_with(values: &str) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_table(&conn).unwrap();
+        conn.execute_batch(&format!("INSERT INTO settings (key, value) VALUES {values}")).unwrap();
+        return conn;
+    }
+
+
+    #[test]
+    fn check_reports_unusable_values() {
+        let conn = settings_with("('large_list_threshold', 'lots'), ('show_ids', 'yes'), ('list_width', 'wide'),
+            ('enter_action', 'open'), ('completed_style', 'sparkly'), ('unknown_setting', 'anything')");
+        assert_eq!(check(&conn), [
+            "large_list_threshold = 'lots' is not a whole number",
+            "show_ids = 'yes' is not true or false",
+            "list_width = 'wide' is not a whole number or full",
+            "enter_action = 'open' is not one of toggle, details",
+            "completed_style = 'sparkly' can't be used, unknown effect 'sparkly'"
+        ]);
+        // every unusable value falls back to its default
+        assert_eq!(get_usize(&conn, LARGE_LIST_THRESHOLD, 1000), 1000);
+        assert!(get_bool(&conn, SHOW_IDS, true));
+    }
+
+
+    #[test]
+    fn check_accepts_unquoted_numbers() {
+        // a number written without quotes is stored as an integer, it is still read as the number it is
+        let conn = settings_with("('large_list_threshold', 500), ('stale_days', 0), ('list_width', 40), ('max_label_width', ' 20 ')");
+        assert!(check(&conn).is_empty());
+        assert_eq!(get_usize(&conn, LARGE_LIST_THRESHOLD, 1000), 500);
+        assert_eq!(get_usize(&conn, MAX_LABEL_WIDTH, 0), 20);
+        // a number isn't an on/off value though
+        let conn = settings_with("('number_keys', 1)");
+        assert_eq!(check(&conn), ["number_keys = '1' is not true or false"]);
+    }
+}